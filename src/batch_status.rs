@@ -0,0 +1,273 @@
+use ethers::{types::H256, utils::rlp};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchState {
+    Committed,
+    Finalized,
+    Reverted,
+}
+
+/// L1 block and transaction in which a batch changed state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L1Provenance {
+    pub block_number: u64,
+    pub tx_hash: H256,
+    /// Timestamp of the L1 block, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Lifecycle of a batch, derived from the `CommitBatch`, `FinalizeBatch` and `RevertBatch`
+/// events. A batch that is committed again after a revert starts a new lifecycle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchStatus {
+    pub batch_index: u64,
+    pub state: BatchState,
+    pub committed: L1Provenance,
+    pub finalized: Option<L1Provenance>,
+    pub reverted: Option<L1Provenance>,
+}
+
+impl BatchStatus {
+    pub fn committed(batch_index: u64, provenance: L1Provenance) -> Self {
+        Self {
+            batch_index,
+            state: BatchState::Committed,
+            committed: provenance,
+            finalized: None,
+            reverted: None,
+        }
+    }
+
+    /// Status of a batch after its `CommitBatch` event is applied over the stored status.
+    /// Replaying the event that committed `previous` keeps the transitions that followed it,
+    /// while a new commit, e.g. after a revert, starts a new lifecycle.
+    pub fn recommitted(previous: Option<Self>, batch_index: u64, provenance: L1Provenance) -> Self {
+        match previous {
+            Some(previous)
+                if previous.committed == provenance && previous.state != BatchState::Reverted =>
+            {
+                previous
+            }
+            _ => Self::committed(batch_index, provenance),
+        }
+    }
+
+    /// Only committed batches can be finalized, a reverted batch stays reverted.
+    pub fn finalize(&mut self, provenance: L1Provenance) {
+        if self.state == BatchState::Committed {
+            self.state = BatchState::Finalized;
+            self.finalized = Some(provenance);
+        }
+    }
+
+    /// Finalized batches can't be reverted.
+    pub fn revert(&mut self, provenance: L1Provenance) {
+        if self.state == BatchState::Committed {
+            self.state = BatchState::Reverted;
+            self.reverted = Some(provenance);
+        }
+    }
+
+    /// Undoes the transitions that happened after `l1_block`, as if the events after it had not
+    /// been synced yet.
+    pub fn rewind(&mut self, l1_block: u64) {
+        let after = |provenance: &Option<L1Provenance>| {
+            provenance
+                .as_ref()
+                .is_some_and(|provenance| provenance.block_number > l1_block)
+        };
+        if after(&self.finalized) {
+            self.finalized = None;
+            self.state = BatchState::Committed;
+        }
+        if after(&self.reverted) {
+            self.reverted = None;
+            self.state = BatchState::Committed;
+        }
+    }
+}
+
+/// Roots of the L2 state after the last block of a finalized batch, from its `FinalizeBatch`
+/// event. When several batches are finalized together, only the last one carries roots.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizedRoots {
+    pub state_root: H256,
+    pub withdraw_root: H256,
+}
+
+impl Encodable for BatchState {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        let state: u8 = match self {
+            BatchState::Committed => 0,
+            BatchState::Finalized => 1,
+            BatchState::Reverted => 2,
+        };
+        stream.append(&state);
+    }
+}
+
+impl Decodable for BatchState {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        match rlp.as_val::<u8>()? {
+            0 => Ok(BatchState::Committed),
+            1 => Ok(BatchState::Finalized),
+            2 => Ok(BatchState::Reverted),
+            _ => Err(DecoderError::Custom("unknown batch state")),
+        }
+    }
+}
+
+impl Encodable for L1Provenance {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.block_number);
+        stream.append(&self.tx_hash);
+        stream.append(&self.timestamp);
+    }
+}
+
+impl Decodable for L1Provenance {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(L1Provenance {
+            block_number: rlp.val_at(0)?,
+            tx_hash: rlp.val_at(1)?,
+            timestamp: rlp.val_at(2)?,
+        })
+    }
+}
+
+/// Transitions that didn't happen are encoded as empty lists.
+fn append_optional(stream: &mut RlpStream, provenance: &Option<L1Provenance>) {
+    match provenance {
+        Some(provenance) => stream.append(provenance),
+        None => stream.begin_list(0),
+    };
+}
+
+fn decode_optional(rlp: &Rlp) -> Result<Option<L1Provenance>, DecoderError> {
+    if rlp.item_count()? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(rlp.as_val()?))
+}
+
+impl Encodable for BatchStatus {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(5);
+        stream.append(&self.batch_index);
+        stream.append(&self.state);
+        stream.append(&self.committed);
+        append_optional(stream, &self.finalized);
+        append_optional(stream, &self.reverted);
+    }
+}
+
+impl Decodable for BatchStatus {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(BatchStatus {
+            batch_index: rlp.val_at(0)?,
+            state: rlp.val_at(1)?,
+            committed: rlp.val_at(2)?,
+            finalized: decode_optional(&rlp.at(3)?)?,
+            reverted: decode_optional(&rlp.at(4)?)?,
+        })
+    }
+}
+
+impl Encodable for FinalizedRoots {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2);
+        stream.append(&self.state_root);
+        stream.append(&self.withdraw_root);
+    }
+}
+
+impl Decodable for FinalizedRoots {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(FinalizedRoots {
+            state_root: rlp.val_at(0)?,
+            withdraw_root: rlp.val_at(1)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::provenance;
+
+    #[test]
+    fn finalize_only_committed_batches() {
+        let mut status = BatchStatus::committed(1, provenance(100));
+        status.finalize(provenance(110));
+        assert_eq!(status.state, BatchState::Finalized);
+        assert_eq!(status.finalized, Some(provenance(110)));
+
+        let mut status = BatchStatus::committed(1, provenance(100));
+        status.revert(provenance(105));
+        status.finalize(provenance(110));
+        assert_eq!(status.state, BatchState::Reverted);
+        assert_eq!(status.finalized, None);
+    }
+
+    #[test]
+    fn revert_only_committed_batches() {
+        let mut status = BatchStatus::committed(1, provenance(100));
+        status.revert(provenance(105));
+        assert_eq!(status.state, BatchState::Reverted);
+        assert_eq!(status.reverted, Some(provenance(105)));
+
+        let mut status = BatchStatus::committed(1, provenance(100));
+        status.finalize(provenance(110));
+        status.revert(provenance(115));
+        assert_eq!(status.state, BatchState::Finalized);
+        assert_eq!(status.reverted, None);
+    }
+
+    #[test]
+    fn rewind_undoes_later_transitions() {
+        let mut finalized = BatchStatus::committed(1, provenance(100));
+        finalized.finalize(provenance(110));
+
+        let mut status = finalized.clone();
+        status.rewind(110);
+        assert_eq!(status, finalized);
+        status.rewind(109);
+        assert_eq!(status, BatchStatus::committed(1, provenance(100)));
+
+        let mut status = BatchStatus::committed(1, provenance(100));
+        status.revert(provenance(105));
+        status.rewind(104);
+        assert_eq!(status, BatchStatus::committed(1, provenance(100)));
+    }
+
+    #[test]
+    fn rlp_round_trip() {
+        let committed = BatchStatus::committed(7, provenance(100));
+        let mut finalized = committed.clone();
+        finalized.finalize(provenance(110));
+        let mut reverted = committed.clone();
+        reverted.revert(provenance(105));
+
+        for status in [committed, finalized, reverted] {
+            let encoded = rlp::encode(&status);
+            assert_eq!(rlp::decode::<BatchStatus>(&encoded).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn missing_transitions_are_empty_lists() {
+        let encoded = rlp::encode(&BatchStatus::committed(7, provenance(100)));
+        let rlp = Rlp::new(&encoded);
+        for index in [3, 4] {
+            assert!(rlp.at(index).unwrap().is_list());
+            assert_eq!(rlp.at(index).unwrap().item_count().unwrap(), 0);
+        }
+    }
+}
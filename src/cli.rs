@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::export::{ExportDataset, ExportFormat};
+
+#[derive(Debug, Parser)]
+#[command(name = "scroll-reth", about = "Scroll L1 follower node")]
+pub struct Cli {
+    /// Directory of the follower database.
+    #[arg(long, global = true, default_value = "scroll-db")]
+    pub datadir: PathBuf,
+
+    /// Runs the node when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Sync L1 messages and batches and serve them (default).
+    Node {
+        /// Keep the synced data in memory instead of the data directory. It is lost on exit,
+        /// which suits short-lived runs and tests.
+        #[arg(long)]
+        in_memory: bool,
+    },
+    /// Serve JSON-RPC from a read-only view of a database synced by another node.
+    Rpc,
+    /// Inspect the database without running the node.
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// Export L1 messages or batches to JSONL, CSV or Parquet.
+    Export(ExportArgs),
+    /// Write every L1 message and batch with the sync cursors to a snapshot file.
+    Snapshot {
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Bootstrap an empty database from a snapshot file.
+    Import {
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Remove the L1 messages and batches synced after an L1 block and reset the cursors to it.
+    Rewind {
+        #[arg(long)]
+        to_l1_block: u64,
+    },
+    /// Check that the L2 block ranges of all stored batches and their chunks are contiguous.
+    Verify,
+    /// Prove an L2 to L1 withdrawal against the latest finalized withdraw root.
+    WithdrawalProof(WithdrawalProofArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[arg(long, value_enum)]
+    pub dataset: ExportDataset,
+
+    #[arg(long, value_enum, default_value = "jsonl")]
+    pub format: ExportFormat,
+
+    /// Output file, or directory of part files for Parquet. A checkpoint is kept next to it
+    /// so that an interrupted export can be resumed by running the same command again.
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// First queue or batch index to export.
+    #[arg(long)]
+    pub from: Option<u64>,
+
+    /// Last queue or batch index to export, everything stored so far if omitted.
+    #[arg(long)]
+    pub to: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct WithdrawalProofArgs {
+    /// File with the withdraw trie leaves, one message hash per line ordered by nonce, e.g.
+    /// from the `AppendMessage` events of the L2MessageQueue contract.
+    #[arg(long)]
+    pub leaves: PathBuf,
+
+    /// Nonce of the withdrawal message.
+    #[arg(long)]
+    pub nonce: u64,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DbCommand {
+    /// Number of entries per table and size of the data file.
+    Stats,
+    /// Print a single decoded entry.
+    Get {
+        #[command(subcommand)]
+        item: GetCommand,
+    },
+    /// Print a range of decoded entries.
+    List {
+        #[command(subcommand)]
+        item: ListCommand,
+    },
+    /// Sync cursors and stored index bounds.
+    Cursors,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GetCommand {
+    /// L1 message by queue index.
+    L1Message { index: u64 },
+    /// Batch by index, with its chunk block ranges.
+    Batch { index: u64 },
+    /// Lifecycle of a batch with the L1 transactions that committed, finalized or reverted it.
+    BatchStatus { index: u64 },
+    /// State root and withdraw root of a finalized batch.
+    FinalizedRoots { index: u64 },
+    /// Context of an L2 block as committed on L1.
+    BlockContext { number: u64 },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ListCommand {
+    /// Batches with an index in `[from, to]`, skipping the ones that are not stored.
+    Batches {
+        #[arg(long)]
+        from: u64,
+        #[arg(long)]
+        to: u64,
+    },
+    /// Committed batches that are not finalized yet.
+    Unfinalized {
+        /// Only list batches committed at least this many hours ago.
+        #[arg(long, default_value_t = 0)]
+        older_than_hours: u64,
+    },
+}
@@ -0,0 +1,363 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Result};
+use reth_db::{
+    cursor::DbCursorRO,
+    mdbx::{DatabaseArguments, DatabaseFlags},
+    models::client_version::ClientVersion,
+    transaction::DbTx,
+    version::{check_db_version_file, create_db_version_file, DatabaseVersionError},
+    DatabaseEnv, DatabaseEnvKind,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    batch_status::{BatchState, BatchStatus, FinalizedRoots},
+    migrations::{check_schema_version, migrate},
+    rollup_sync_service_util::{BlockContext, ChunkBlockRange},
+    sync_service::L1MessageTx,
+    tables::{
+        BatchBlockRanges, BatchBlocks, BatchChunkRanges, BatchFinalizedRoots,
+        BatchL1MessagesPopped, BatchStatuses, BlockContexts, L1MessageBlocks, L1Messages, Metadata,
+        TABLES,
+    },
+};
+
+/// Keys of the `Metadata` table.
+pub const LAST_SYNCED_L1_BLOCK_KEY: &str = "LastSyncedL1BlockNumber";
+pub const LAST_L1_MESSAGE_INDEX_KEY: &str = "LastL1MessageQueueIndex";
+/// Version of the key and value formats, see `migrations`.
+pub const SCHEMA_VERSION_KEY: &str = "SchemaVersion";
+pub const ROLLUP_LAST_SYNCED_L1_BLOCK_KEY: &str = "RLastRollupEventSyncedL1BlockNumber";
+pub const FIRST_COMMITTED_BATCH_INDEX_KEY: &str = "RFirstCommittedBatchIndex";
+pub const LAST_COMMITTED_BATCH_INDEX_KEY: &str = "RLastCommittedBatchIndex";
+pub const LAST_FINALIZED_BATCH_INDEX_KEY: &str = "RLastFinalizedBatchIndex";
+
+/// Opens the database for reading and writing, creating it if it doesn't exist. Existing
+/// databases are checked against their version file, so that one written by an incompatible
+/// version of the storage layer is refused instead of being misread, and migrated to the
+/// current schema version.
+pub fn init_db(path: &Path) -> Result<DatabaseEnv> {
+    let exists = database_exists(path);
+    if exists {
+        info!("Opening existing database at {}", path.display());
+        check_version(path, true)?;
+    } else {
+        info!("Creating new database at {}", path.display());
+        fs::create_dir_all(path)?;
+        create_db_version_file(path)?;
+    }
+    let db = DatabaseEnv::open(path, DatabaseEnvKind::RW, database_arguments())?;
+    create_tables(&db)?;
+    migrate(&db, !exists)?;
+    Ok(db)
+}
+
+/// Opens an existing database without taking the write lock. MDBX allows any number of
+/// readers next to the writer, including in other processes, and every read transaction sees
+/// the data committed when it started, so this is safe to use while the node is syncing.
+pub fn open_db_read_only(path: &Path) -> Result<DatabaseEnv> {
+    if !database_exists(path) {
+        bail!("No database at {}", path.display());
+    }
+    check_version(path, false)?;
+    let db = DatabaseEnv::open(path, DatabaseEnvKind::RO, database_arguments())?;
+    check_schema_version(&db)?;
+    Ok(db)
+}
+
+/// Only the follower's own tables are created, none of reth's node tables.
+fn create_tables(db: &DatabaseEnv) -> Result<()> {
+    let tx = db.begin_rw_txn()?;
+    for table in TABLES {
+        tx.create_db(Some(table), DatabaseFlags::default())?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn database_exists(path: &Path) -> bool {
+    path.join("mdbx.dat").exists()
+}
+
+/// Databases created before the version file was introduced don't have one. It is written
+/// the next time the database is opened for writing.
+fn check_version(path: &Path, create_missing: bool) -> Result<()> {
+    match check_db_version_file(path) {
+        Ok(()) => Ok(()),
+        Err(DatabaseVersionError::MissingFile) if create_missing => {
+            Ok(create_db_version_file(path)?)
+        }
+        Err(DatabaseVersionError::MissingFile) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn client_version() -> ClientVersion {
+    ClientVersion {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        ..Default::default()
+    }
+}
+
+fn database_arguments() -> DatabaseArguments {
+    DatabaseArguments::new(client_version())
+}
+
+/// A committed batch with the L2 block range covered by its chunks. The chunks are empty once
+/// they have been pruned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Batch {
+    pub batch_index: u64,
+    pub first_block: u64,
+    pub last_block: u64,
+    pub chunks: Vec<ChunkBlockRange>,
+}
+
+impl Batch {
+    /// `None` for a batch without chunks, which has no block range.
+    pub fn from_chunks(batch_index: u64, chunks: Vec<ChunkBlockRange>) -> Option<Self> {
+        let first_block = chunks.first()?.start_block_number;
+        let last_block = chunks.last()?.end_block_number;
+        Some(Self {
+            batch_index,
+            first_block,
+            last_block,
+            chunks,
+        })
+    }
+}
+
+/// Roots of a finalized batch along with the last L2 block they cover.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizedBatchRoots {
+    pub batch_index: u64,
+    pub last_block: u64,
+    #[serde(flatten)]
+    pub roots: FinalizedRoots,
+}
+
+/// Sync cursors and index bounds stored alongside the data.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cursors {
+    pub last_synced_l1_block: Option<u64>,
+    pub rollup_last_synced_l1_block: Option<u64>,
+    pub last_l1_message_index: Option<u64>,
+    pub first_committed_batch_index: Option<u64>,
+    pub last_committed_batch_index: Option<u64>,
+    pub last_finalized_batch_index: Option<u64>,
+}
+
+pub fn get_l1_message<TX: DbTx>(tx: &TX, queue_index: u64) -> Result<Option<L1MessageTx>> {
+    Ok(tx.get::<L1Messages>(queue_index)?)
+}
+
+/// Number of messages stored with a queue index in `[from, to]`.
+pub fn count_l1_messages_in_range<TX: DbTx>(tx: &TX, from: u64, to: u64) -> Result<u64> {
+    if from > to {
+        return Ok(0);
+    }
+    let mut count = 0;
+    for entry in tx.cursor_read::<L1Messages>()?.walk_range(from..=to)? {
+        entry?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// `None` if no message is stored.
+pub fn get_first_l1_message_index<TX: DbTx>(tx: &TX) -> Result<Option<u64>> {
+    Ok(tx
+        .cursor_read::<L1Messages>()?
+        .first()?
+        .map(|(queue_index, _)| queue_index))
+}
+
+/// `None` for databases created before the schema was versioned.
+pub fn get_schema_version<TX: DbTx>(tx: &TX) -> Result<Option<u64>> {
+    Ok(tx.get::<Metadata>(SCHEMA_VERSION_KEY.to_string())?)
+}
+
+pub fn get_last_l1_message_index<TX: DbTx>(tx: &TX) -> Result<Option<u64>> {
+    Ok(tx.get::<Metadata>(LAST_L1_MESSAGE_INDEX_KEY.to_string())?)
+}
+
+/// L1 block the message was emitted in, `None` for messages synced before it was recorded.
+pub fn get_l1_message_block<TX: DbTx>(tx: &TX, queue_index: u64) -> Result<Option<u64>> {
+    Ok(tx.get::<L1MessageBlocks>(queue_index)?)
+}
+
+pub fn get_batch_chunk_ranges<TX: DbTx>(
+    tx: &TX,
+    batch_index: u64,
+) -> Result<Option<Vec<ChunkBlockRange>>> {
+    Ok(tx
+        .get::<BatchChunkRanges>(batch_index)?
+        .map(|chunk_ranges| chunk_ranges.0))
+}
+
+pub fn get_batch<TX: DbTx>(tx: &TX, batch_index: u64) -> Result<Option<Batch>> {
+    if let Some(chunks) = get_batch_chunk_ranges(tx, batch_index)? {
+        return Ok(Batch::from_chunks(batch_index, chunks));
+    }
+    // Only the block range is left of a pruned batch.
+    Ok(get_batch_block_range(tx, batch_index)?.map(|range| Batch {
+        batch_index,
+        first_block: range.start_block_number,
+        last_block: range.end_block_number,
+        chunks: vec![],
+    }))
+}
+
+/// L2 block range of a batch, kept when its chunk ranges are pruned.
+pub fn get_batch_block_range<TX: DbTx>(
+    tx: &TX,
+    batch_index: u64,
+) -> Result<Option<ChunkBlockRange>> {
+    Ok(tx.get::<BatchBlockRanges>(batch_index)?)
+}
+
+/// Total number of L1 messages popped up to and including the batch, `None` for batches synced
+/// before it was recorded.
+pub fn get_batch_total_l1_messages_popped<TX: DbTx>(
+    tx: &TX,
+    batch_index: u64,
+) -> Result<Option<u64>> {
+    Ok(tx.get::<BatchL1MessagesPopped>(batch_index)?)
+}
+
+/// L1 block the batch was committed in, `None` for batches synced before it was recorded.
+pub fn get_batch_l1_block<TX: DbTx>(tx: &TX, batch_index: u64) -> Result<Option<u64>> {
+    Ok(tx.get::<BatchBlocks>(batch_index)?)
+}
+
+/// `None` for blocks of batches synced before block contexts were recorded.
+pub fn get_block_context<TX: DbTx>(tx: &TX, l2_block_number: u64) -> Result<Option<BlockContext>> {
+    Ok(tx.get::<BlockContexts>(l2_block_number)?)
+}
+
+/// Stored contexts of the blocks in `range`, in block order.
+pub fn get_block_contexts_in_range<TX: DbTx>(
+    tx: &TX,
+    range: &ChunkBlockRange,
+) -> Result<Vec<BlockContext>> {
+    let mut cursor = tx.cursor_read::<BlockContexts>()?;
+    let mut contexts = vec![];
+    for entry in cursor.walk_range(range.start_block_number..=range.end_block_number)? {
+        contexts.push(entry?.1);
+    }
+    Ok(contexts)
+}
+
+/// `None` for batches synced before their lifecycle was tracked.
+pub fn get_batch_status<TX: DbTx>(tx: &TX, batch_index: u64) -> Result<Option<BatchStatus>> {
+    Ok(tx.get::<BatchStatuses>(batch_index)?)
+}
+
+/// `None` if the batch isn't finalized, or was finalized together with later batches.
+pub fn get_finalized_roots<TX: DbTx>(tx: &TX, batch_index: u64) -> Result<Option<FinalizedRoots>> {
+    Ok(tx.get::<BatchFinalizedRoots>(batch_index)?)
+}
+
+/// Roots of the latest finalized batch that has them.
+pub fn get_latest_finalized_roots<TX: DbTx>(tx: &TX) -> Result<Option<FinalizedBatchRoots>> {
+    let Some((batch_index, roots)) = tx.cursor_read::<BatchFinalizedRoots>()?.last()? else {
+        return Ok(None);
+    };
+    Ok(
+        get_batch_block_range(tx, batch_index)?.map(|range| FinalizedBatchRoots {
+            batch_index,
+            last_block: range.end_block_number,
+            roots,
+        }),
+    )
+}
+
+pub fn get_last_finalized_batch_index<TX: DbTx>(tx: &TX) -> Result<Option<u64>> {
+    Ok(tx.get::<Metadata>(LAST_FINALIZED_BATCH_INDEX_KEY.to_string())?)
+}
+
+/// Batches that are committed but not finalized, and were committed in an L1 block older than
+/// `committed_before` (a Unix timestamp). Batches are finalized in order, so only the ones
+/// after the last finalized batch are looked at.
+pub fn get_unfinalized_batches<TX: DbTx>(
+    tx: &TX,
+    committed_before: u64,
+) -> Result<Vec<BatchStatus>> {
+    let Some((first, last)) = get_committed_batch_bounds(tx)? else {
+        return Ok(vec![]);
+    };
+    let from = get_last_finalized_batch_index(tx)?.map_or(first, |finalized| finalized + 1);
+    let mut batches = vec![];
+    for batch_index in from..=last {
+        if let Some(status) = get_batch_status(tx, batch_index)? {
+            if status.state == BatchState::Committed
+                && status.committed.timestamp < committed_before
+            {
+                batches.push(status);
+            }
+        }
+    }
+    Ok(batches)
+}
+
+pub fn get_cursors<TX: DbTx>(tx: &TX) -> Result<Cursors> {
+    let bounds = get_committed_batch_bounds(tx)?;
+    Ok(Cursors {
+        last_synced_l1_block: tx.get::<Metadata>(LAST_SYNCED_L1_BLOCK_KEY.to_string())?,
+        rollup_last_synced_l1_block: tx
+            .get::<Metadata>(ROLLUP_LAST_SYNCED_L1_BLOCK_KEY.to_string())?,
+        last_l1_message_index: get_last_l1_message_index(tx)?,
+        first_committed_batch_index: bounds.map(|(first, _)| first),
+        last_committed_batch_index: bounds.map(|(_, last)| last),
+        last_finalized_batch_index: get_last_finalized_batch_index(tx)?,
+    })
+}
+
+/// Lowest and highest index of the batches committed since the node started tracking them.
+pub fn get_committed_batch_bounds<TX: DbTx>(tx: &TX) -> Result<Option<(u64, u64)>> {
+    let first = tx.get::<Metadata>(FIRST_COMMITTED_BATCH_INDEX_KEY.to_string())?;
+    let last = tx.get::<Metadata>(LAST_COMMITTED_BATCH_INDEX_KEY.to_string())?;
+    Ok(first.zip(last))
+}
+
+/// Binary searches the committed batches for the one containing `l2_block_number`. Batches
+/// cover increasing, non-overlapping L2 block ranges, so this takes O(log n) lookups.
+pub fn find_batch_for_block<TX: DbTx>(tx: &TX, l2_block_number: u64) -> Result<Option<u64>> {
+    let Some((mut low, mut high)) = get_committed_batch_bounds(tx)? else {
+        return Ok(None);
+    };
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        // A batch without a block range (e.g. committed without chunks) can't be placed, so look
+        // for the closest stored batch above it instead.
+        let Some((batch_index, range)) = (mid..=high).find_map(|index| {
+            get_batch_block_range(tx, index)
+                .ok()
+                .flatten()
+                .map(|range| (index, range))
+        }) else {
+            if mid == 0 {
+                break;
+            }
+            high = mid - 1;
+            continue;
+        };
+
+        if l2_block_number < range.start_block_number {
+            if mid == 0 {
+                break;
+            }
+            high = mid - 1;
+        } else if l2_block_number > range.end_block_number {
+            low = batch_index + 1;
+        } else {
+            return Ok(Some(batch_index));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,95 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use reth_db::{database::Database, table::Table, transaction::DbTx};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    cli::{DbCommand, GetCommand, ListCommand},
+    db::{
+        get_batch, get_batch_status, get_block_context, get_cursors, get_finalized_roots,
+        get_l1_message, get_schema_version, get_unfinalized_batches, open_db_read_only,
+    },
+    tables::{
+        BatchBlockRanges, BatchBlocks, BatchChunkRanges, BatchFinalizedRoots,
+        BatchL1MessagesPopped, BatchStatuses, BlockContexts, L1MessageBlocks, L1Messages, Metadata,
+    },
+};
+
+/// Runs a `db` subcommand against a read-only view of the database, so it is safe to use
+/// while the node is running.
+pub fn run_db_command(datadir: &Path, command: DbCommand) -> Result<()> {
+    let db = open_db_read_only(datadir)?;
+    let tx = db.tx()?;
+
+    match command {
+        DbCommand::Stats => {
+            let size = fs::metadata(datadir.join("mdbx.dat"))
+                .map(|metadata| metadata.len())
+                .ok();
+            print_json(&json!({
+                "tables": {
+                    (Metadata::NAME): tx.entries::<Metadata>()?,
+                    (L1Messages::NAME): tx.entries::<L1Messages>()?,
+                    (L1MessageBlocks::NAME): tx.entries::<L1MessageBlocks>()?,
+                    (BatchChunkRanges::NAME): tx.entries::<BatchChunkRanges>()?,
+                    (BatchBlocks::NAME): tx.entries::<BatchBlocks>()?,
+                    (BatchBlockRanges::NAME): tx.entries::<BatchBlockRanges>()?,
+                    (BatchL1MessagesPopped::NAME): tx.entries::<BatchL1MessagesPopped>()?,
+                    (BatchStatuses::NAME): tx.entries::<BatchStatuses>()?,
+                    (BatchFinalizedRoots::NAME): tx.entries::<BatchFinalizedRoots>()?,
+                    (BlockContexts::NAME): tx.entries::<BlockContexts>()?,
+                },
+                "dataFileSizeBytes": size,
+                "schemaVersion": get_schema_version(&tx)?,
+            }))
+        }
+        DbCommand::Get {
+            item: GetCommand::L1Message { index },
+        } => print_json(&get_l1_message(&tx, index)?),
+        DbCommand::Get {
+            item: GetCommand::Batch { index },
+        } => print_json(&get_batch(&tx, index)?),
+        DbCommand::Get {
+            item: GetCommand::BatchStatus { index },
+        } => print_json(&get_batch_status(&tx, index)?),
+        DbCommand::Get {
+            item: GetCommand::FinalizedRoots { index },
+        } => print_json(&get_finalized_roots(&tx, index)?),
+        DbCommand::Get {
+            item: GetCommand::BlockContext { number },
+        } => print_json(&get_block_context(&tx, number)?),
+        DbCommand::List {
+            item: ListCommand::Batches { from, to },
+        } => {
+            if to < from {
+                bail!("--to must not be lower than --from");
+            }
+            let mut batches = vec![];
+            for batch_index in from..=to {
+                if let Some(batch) = get_batch(&tx, batch_index)? {
+                    batches.push(batch);
+                }
+            }
+            print_json(&batches)
+        }
+        DbCommand::List {
+            item: ListCommand::Unfinalized { older_than_hours },
+        } => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let committed_before = now.saturating_sub(older_than_hours * 3600);
+            print_json(&get_unfinalized_batches(&tx, committed_before)?)
+        }
+        DbCommand::Cursors => print_json(&get_cursors(&tx)?),
+    }
+}
+
+pub(crate) fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -0,0 +1,595 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use alloy_primitives::hex;
+use anyhow::{bail, Result};
+use arrow::{
+    array::{ArrayRef, BinaryArray, ListBuilder, StringArray, UInt64Array, UInt64Builder},
+    record_batch::RecordBatch,
+};
+use clap::ValueEnum;
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use reth_db::{database::Database, transaction::DbTx};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    cli::ExportArgs,
+    db::{
+        get_batch, get_committed_batch_bounds, get_first_l1_message_index, get_l1_message,
+        get_last_l1_message_index, open_db_read_only, Batch,
+    },
+    sync_service::L1MessageTx,
+};
+
+/// Number of queue or batch indices read per chunk. The checkpoint is written after every chunk,
+/// so this is also the most work an interrupted export has to redo.
+const ROWS_PER_CHUNK: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportDataset {
+    L1Messages,
+    Batches,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+    Parquet,
+}
+
+/// Progress of an export, stored next to the output so that an interrupted or incremental
+/// export continues where the previous run stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Checkpoint {
+    dataset: ExportDataset,
+    format: ExportFormat,
+    from: Option<u64>,
+    to: Option<u64>,
+    /// First index that has not been exported yet.
+    next_index: u64,
+    /// Length of the output file after the last completed chunk. Anything past it was written
+    /// by an interrupted chunk and is truncated on resume. Unused for Parquet.
+    bytes_written: u64,
+    /// Number of Parquet part files written.
+    parts: u64,
+}
+
+impl Checkpoint {
+    fn matches(&self, args: &ExportArgs) -> bool {
+        self.dataset == args.dataset
+            && self.format == args.format
+            && self.from == args.from
+            && self.to == args.to
+    }
+}
+
+enum Rows {
+    L1Messages(Vec<L1MessageTx>),
+    Batches(Vec<Batch>),
+}
+
+impl Rows {
+    fn len(&self) -> usize {
+        match self {
+            Rows::L1Messages(messages) => messages.len(),
+            Rows::Batches(batches) => batches.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct L1MessageRow {
+    queue_index: u64,
+    gas: String,
+    to: String,
+    value: String,
+    data: String,
+    sender: String,
+}
+
+impl From<&L1MessageTx> for L1MessageRow {
+    fn from(message: &L1MessageTx) -> Self {
+        Self {
+            queue_index: message.queue_index,
+            gas: message.gas.to_string(),
+            to: message.to.to_string(),
+            value: message.value.to_string(),
+            data: hex::encode_prefixed(&message.data),
+            sender: message.sender.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BatchRow {
+    batch_index: u64,
+    first_block: u64,
+    last_block: u64,
+    num_chunks: u64,
+    /// Chunk block ranges as a JSON array, CSV has no nested values.
+    chunks: String,
+}
+
+impl TryFrom<&Batch> for BatchRow {
+    type Error = serde_json::Error;
+
+    fn try_from(batch: &Batch) -> Result<Self, Self::Error> {
+        Ok(Self {
+            batch_index: batch.batch_index,
+            first_block: batch.first_block,
+            last_block: batch.last_block,
+            num_chunks: batch.chunks.len() as u64,
+            chunks: serde_json::to_string(&batch.chunks)?,
+        })
+    }
+}
+
+/// Exports the stored L1 messages or batches with an index in `[from, to]`, starting at the
+/// first stored index without `--from`. Missing indices (pruned messages, batches committed
+/// without chunks) are skipped. Without `--to`, everything stored so far is exported, and
+/// running the same command again later appends what was synced in the meantime.
+pub fn run_export(datadir: &Path, args: ExportArgs) -> Result<()> {
+    let db = open_db_read_only(datadir)?;
+    let checkpoint_path = checkpoint_path(&args.output, args.format);
+
+    let mut checkpoint = match fs::read(&checkpoint_path) {
+        Ok(contents) => {
+            let checkpoint: Checkpoint = serde_json::from_slice(&contents)?;
+            if !checkpoint.matches(&args) {
+                bail!(
+                    "{} was written with different export options, remove the output and the checkpoint to start over",
+                    checkpoint_path.display()
+                );
+            }
+            info!("Resuming export at index {}", checkpoint.next_index);
+            checkpoint
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            if args.output.exists() {
+                bail!("{} already exists", args.output.display());
+            }
+            let first_index = match (args.from, args.dataset) {
+                (Some(from), _) => from,
+                // Messages queued before the genesis block or pruned are not stored.
+                (None, ExportDataset::L1Messages) => {
+                    get_first_l1_message_index(&db.tx()?)?.unwrap_or(0)
+                }
+                (None, ExportDataset::Batches) => {
+                    get_committed_batch_bounds(&db.tx()?)?.map_or(0, |(first, _)| first)
+                }
+            };
+            Checkpoint {
+                dataset: args.dataset,
+                format: args.format,
+                from: args.from,
+                to: args.to,
+                next_index: first_index,
+                bytes_written: 0,
+                parts: 0,
+            }
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let last_stored_index = {
+        let tx = db.tx()?;
+        match args.dataset {
+            ExportDataset::L1Messages => get_last_l1_message_index(&tx)?,
+            ExportDataset::Batches => get_committed_batch_bounds(&tx)?.map(|(_, last)| last),
+        }
+    };
+    let Some(last_index) = last_stored_index.map(|last| args.to.map_or(last, |to| to.min(last)))
+    else {
+        info!("Nothing to export");
+        return Ok(());
+    };
+
+    let mut writer = Writer::open(args.format, &args.output, &checkpoint)?;
+    let mut exported = 0;
+    while checkpoint.next_index <= last_index {
+        let chunk_end = last_index.min(checkpoint.next_index + ROWS_PER_CHUNK - 1);
+        // A read transaction per chunk, long-lived readers keep MDBX from reusing pages while
+        // the node is writing.
+        let rows = read_rows(&db.tx()?, args.dataset, checkpoint.next_index, chunk_end)?;
+        exported += rows.len();
+        writer.write(&rows, &mut checkpoint)?;
+
+        checkpoint.next_index = chunk_end + 1;
+        write_checkpoint(&checkpoint_path, &checkpoint)?;
+        info!(
+            "Exported up to index {} of {} ({} rows)",
+            chunk_end, last_index, exported
+        );
+    }
+    info!("Export to {} complete", args.output.display());
+    Ok(())
+}
+
+fn read_rows<TX: DbTx>(tx: &TX, dataset: ExportDataset, from: u64, to: u64) -> Result<Rows> {
+    Ok(match dataset {
+        ExportDataset::L1Messages => {
+            let mut messages = vec![];
+            for queue_index in from..=to {
+                if let Some(message) = get_l1_message(tx, queue_index)? {
+                    messages.push(message);
+                }
+            }
+            Rows::L1Messages(messages)
+        }
+        ExportDataset::Batches => {
+            let mut batches = vec![];
+            for batch_index in from..=to {
+                if let Some(batch) = get_batch(tx, batch_index)? {
+                    batches.push(batch);
+                }
+            }
+            Rows::Batches(batches)
+        }
+    })
+}
+
+fn checkpoint_path(output: &Path, format: ExportFormat) -> PathBuf {
+    match format {
+        ExportFormat::Parquet => output.join("_checkpoint.json"),
+        ExportFormat::Jsonl | ExportFormat::Csv => {
+            let mut path = OsString::from(output);
+            path.push(".checkpoint");
+            path.into()
+        }
+    }
+}
+
+/// Replaces the checkpoint atomically, so a crash leaves either the old or the new one.
+fn write_checkpoint(path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let mut tmp_path = OsString::from(path);
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    fs::write(&tmp_path, serde_json::to_vec(checkpoint)?)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+enum Writer {
+    Jsonl(File),
+    Csv(File),
+    /// Every chunk goes to its own part file, a Parquet file can't be appended to.
+    Parquet(PathBuf),
+}
+
+impl Writer {
+    fn open(format: ExportFormat, output: &Path, checkpoint: &Checkpoint) -> Result<Self> {
+        if format == ExportFormat::Parquet {
+            fs::create_dir_all(output)?;
+            return Ok(Writer::Parquet(output.to_path_buf()));
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(output)?;
+        file.set_len(checkpoint.bytes_written)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(match format {
+            ExportFormat::Csv => Writer::Csv(file),
+            _ => Writer::Jsonl(file),
+        })
+    }
+
+    /// Writes a chunk and syncs it to disk before the checkpoint is moved past it.
+    fn write(&mut self, rows: &Rows, checkpoint: &mut Checkpoint) -> Result<()> {
+        match self {
+            Writer::Jsonl(file) => {
+                let mut buffer = vec![];
+                match rows {
+                    Rows::L1Messages(messages) => {
+                        for message in messages {
+                            serde_json::to_writer(&mut buffer, message)?;
+                            buffer.push(b'\n');
+                        }
+                    }
+                    Rows::Batches(batches) => {
+                        for batch in batches {
+                            serde_json::to_writer(&mut buffer, batch)?;
+                            buffer.push(b'\n');
+                        }
+                    }
+                }
+                checkpoint.bytes_written = append(file, &buffer)?;
+            }
+            Writer::Csv(file) => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(checkpoint.bytes_written == 0)
+                    .from_writer(vec![]);
+                match rows {
+                    Rows::L1Messages(messages) => {
+                        for message in messages {
+                            writer.serialize(L1MessageRow::from(message))?;
+                        }
+                    }
+                    Rows::Batches(batches) => {
+                        for batch in batches {
+                            writer.serialize(BatchRow::try_from(batch)?)?;
+                        }
+                    }
+                }
+                let buffer = writer.into_inner().map_err(|err| err.into_error())?;
+                checkpoint.bytes_written = append(file, &buffer)?;
+            }
+            Writer::Parquet(dir) => {
+                if rows.is_empty() {
+                    return Ok(());
+                }
+                let record_batch = match rows {
+                    Rows::L1Messages(messages) => l1_messages_record_batch(messages)?,
+                    Rows::Batches(batches) => batches_record_batch(batches)?,
+                };
+                let file = File::create(dir.join(format!("part-{:05}.parquet", checkpoint.parts)))?;
+                let sync_handle = file.try_clone()?;
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let mut writer =
+                    ArrowWriter::try_new(file, record_batch.schema(), Some(properties))?;
+                writer.write(&record_batch)?;
+                writer.close()?;
+                sync_handle.sync_all()?;
+                checkpoint.parts += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends `buffer` and returns the new length of the file.
+fn append(file: &mut File, buffer: &[u8]) -> Result<u64> {
+    file.write_all(buffer)?;
+    file.sync_data()?;
+    Ok(file.stream_position()?)
+}
+
+fn l1_messages_record_batch(messages: &[L1MessageTx]) -> Result<RecordBatch> {
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        (
+            "queue_index",
+            Arc::new(UInt64Array::from_iter_values(
+                messages.iter().map(|message| message.queue_index),
+            )),
+        ),
+        (
+            "gas",
+            Arc::new(StringArray::from_iter_values(
+                messages.iter().map(|message| message.gas.to_string()),
+            )),
+        ),
+        (
+            "to",
+            Arc::new(StringArray::from_iter_values(
+                messages.iter().map(|message| message.to.to_string()),
+            )),
+        ),
+        (
+            "value",
+            Arc::new(StringArray::from_iter_values(
+                messages.iter().map(|message| message.value.to_string()),
+            )),
+        ),
+        (
+            "data",
+            Arc::new(BinaryArray::from_iter_values(
+                messages.iter().map(|message| message.data.as_ref()),
+            )),
+        ),
+        (
+            "sender",
+            Arc::new(StringArray::from_iter_values(
+                messages.iter().map(|message| message.sender.to_string()),
+            )),
+        ),
+    ];
+    Ok(RecordBatch::try_from_iter(columns)?)
+}
+
+fn batches_record_batch(batches: &[Batch]) -> Result<RecordBatch> {
+    let mut chunk_start_blocks = ListBuilder::new(UInt64Builder::new());
+    let mut chunk_end_blocks = ListBuilder::new(UInt64Builder::new());
+    for batch in batches {
+        for chunk in &batch.chunks {
+            chunk_start_blocks
+                .values()
+                .append_value(chunk.start_block_number);
+            chunk_end_blocks
+                .values()
+                .append_value(chunk.end_block_number);
+        }
+        chunk_start_blocks.append(true);
+        chunk_end_blocks.append(true);
+    }
+
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        (
+            "batch_index",
+            Arc::new(UInt64Array::from_iter_values(
+                batches.iter().map(|batch| batch.batch_index),
+            )),
+        ),
+        (
+            "first_block",
+            Arc::new(UInt64Array::from_iter_values(
+                batches.iter().map(|batch| batch.first_block),
+            )),
+        ),
+        (
+            "last_block",
+            Arc::new(UInt64Array::from_iter_values(
+                batches.iter().map(|batch| batch.last_block),
+            )),
+        ),
+        ("chunk_start_blocks", Arc::new(chunk_start_blocks.finish())),
+        ("chunk_end_blocks", Arc::new(chunk_end_blocks.finish())),
+    ];
+    Ok(RecordBatch::try_from_iter(columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        db::init_db,
+        storage::{MdbxStorage, Storage},
+        test_utils::message,
+    };
+
+    /// Stores the messages `queue_indices`, one per L1 block. The storage is dropped before
+    /// returning so that the export can open the database.
+    fn sync_messages(datadir: &TempDir, queue_indices: std::ops::Range<u64>) {
+        let storage = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        let last_block = 100 + queue_indices.end;
+        storage
+            .commit_l1_messages(
+                queue_indices
+                    .map(|queue_index| (message(queue_index), 100 + queue_index))
+                    .collect(),
+                last_block,
+            )
+            .unwrap();
+    }
+
+    fn args(format: ExportFormat, output: &Path, from: Option<u64>) -> ExportArgs {
+        ExportArgs {
+            dataset: ExportDataset::L1Messages,
+            format,
+            output: output.to_path_buf(),
+            from,
+            to: None,
+        }
+    }
+
+    fn exported_queue_indices(output: &Path) -> Vec<u64> {
+        fs::read_to_string(output)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<L1MessageTx>(line)
+                    .unwrap()
+                    .queue_index
+            })
+            .collect()
+    }
+
+    fn read_checkpoint(path: &Path) -> Checkpoint {
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn export_starts_at_the_first_stored_message() {
+        let datadir = TempDir::new().unwrap();
+        let output = datadir.path().join("messages.jsonl");
+        // Messages 0 to 4 were queued before the genesis block.
+        sync_messages(&datadir, 5..10);
+
+        run_export(datadir.path(), args(ExportFormat::Jsonl, &output, None)).unwrap();
+
+        assert_eq!(exported_queue_indices(&output), vec![5, 6, 7, 8, 9]);
+        let checkpoint = read_checkpoint(&checkpoint_path(&output, ExportFormat::Jsonl));
+        assert_eq!(checkpoint.next_index, 10);
+        assert_eq!(
+            checkpoint.bytes_written,
+            fs::metadata(&output).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn export_resumes_at_the_checkpoint() {
+        let datadir = TempDir::new().unwrap();
+        let output = datadir.path().join("messages.jsonl");
+        sync_messages(&datadir, 0..3);
+        run_export(datadir.path(), args(ExportFormat::Jsonl, &output, None)).unwrap();
+
+        sync_messages(&datadir, 3..6);
+        run_export(datadir.path(), args(ExportFormat::Jsonl, &output, None)).unwrap();
+
+        assert_eq!(exported_queue_indices(&output), vec![0, 1, 2, 3, 4, 5]);
+        let checkpoint = read_checkpoint(&checkpoint_path(&output, ExportFormat::Jsonl));
+        assert_eq!(checkpoint.next_index, 6);
+    }
+
+    #[test]
+    fn export_truncates_an_interrupted_chunk() {
+        let datadir = TempDir::new().unwrap();
+        let output = datadir.path().join("messages.jsonl");
+        sync_messages(&datadir, 0..3);
+        run_export(datadir.path(), args(ExportFormat::Jsonl, &output, None)).unwrap();
+
+        // A chunk was partially written but the checkpoint was not moved past it.
+        let mut file = OpenOptions::new().append(true).open(&output).unwrap();
+        file.write_all(b"{\"queueIndex\":3,").unwrap();
+        drop(file);
+
+        sync_messages(&datadir, 3..5);
+        run_export(datadir.path(), args(ExportFormat::Jsonl, &output, None)).unwrap();
+
+        assert_eq!(exported_queue_indices(&output), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn export_overwrites_an_interrupted_parquet_part() {
+        let datadir = TempDir::new().unwrap();
+        let output = datadir.path().join("messages");
+        sync_messages(&datadir, 0..3);
+        run_export(datadir.path(), args(ExportFormat::Parquet, &output, None)).unwrap();
+
+        // The next part was partially written but not recorded in the checkpoint.
+        fs::write(output.join("part-00001.parquet"), b"PAR1").unwrap();
+
+        sync_messages(&datadir, 3..5);
+        run_export(datadir.path(), args(ExportFormat::Parquet, &output, None)).unwrap();
+
+        let checkpoint = read_checkpoint(&checkpoint_path(&output, ExportFormat::Parquet));
+        assert_eq!(checkpoint.parts, 2);
+        let rows: Vec<_> = (0..2)
+            .map(|part| {
+                let file = File::open(output.join(format!("part-{:05}.parquet", part))).unwrap();
+                SerializedFileReader::new(file)
+                    .unwrap()
+                    .metadata()
+                    .file_metadata()
+                    .num_rows()
+            })
+            .collect();
+        assert_eq!(rows, vec![3, 2]);
+    }
+
+    #[test]
+    fn export_with_different_options_fails() {
+        let datadir = TempDir::new().unwrap();
+        let output = datadir.path().join("messages.jsonl");
+        sync_messages(&datadir, 0..3);
+        run_export(datadir.path(), args(ExportFormat::Jsonl, &output, None)).unwrap();
+
+        let err =
+            run_export(datadir.path(), args(ExportFormat::Jsonl, &output, Some(1))).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("was written with different export options"));
+        // The previous export is left untouched.
+        assert_eq!(exported_queue_indices(&output), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    cmp::min,
+    env,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ethers::{
+    providers::{Http, Middleware, Provider, ProviderError},
+    types::{Transaction, TransactionReceipt, H256},
+};
+use metrics::{counter, histogram};
+use tokio::time::Instant;
+use tracing::info;
+
+/// Compute units charged by the provider for the calls issued by the follower.
+/// Values follow the Alchemy compute unit table, which is the strictest we run against.
+pub const BLOCK_NUMBER_CU: u32 = 10;
+pub const GET_BLOCK_RECEIPTS_CU: u32 = 500;
+pub const GET_TRANSACTION_CU: u32 = 17;
+pub const GET_BLOCK_BY_NUMBER_CU: u32 = 16;
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_RANGE_SIZE: u64 = 100;
+
+#[derive(Debug, Clone)]
+pub struct FetcherConfig {
+    /// Maximum number of block ranges fetched at the same time.
+    pub concurrency: usize,
+    /// Number of blocks in a single range.
+    pub range_size: u64,
+    /// Request budget per second, unlimited if `None`.
+    pub requests_per_second: Option<u32>,
+    /// Compute unit budget per second, unlimited if `None`.
+    pub compute_units_per_second: Option<u32>,
+}
+
+impl Default for FetcherConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            range_size: DEFAULT_RANGE_SIZE,
+            requests_per_second: None,
+            compute_units_per_second: None,
+        }
+    }
+}
+
+impl FetcherConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            concurrency: parse_env("L1_FETCH_CONCURRENCY")
+                .unwrap_or(default.concurrency)
+                .max(1),
+            range_size: parse_env("L1_FETCH_RANGE_SIZE")
+                .unwrap_or(default.range_size)
+                .max(1),
+            requests_per_second: parse_env("L1_RPC_REQUESTS_PER_SECOND"),
+            compute_units_per_second: parse_env("L1_RPC_COMPUTE_UNITS_PER_SECOND"),
+        }
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(key: &str) -> Option<T> {
+    env::var(key).ok().and_then(|value| value.parse().ok())
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_sec: u32) -> Self {
+        let per_sec = per_sec.max(1) as f64;
+        Self {
+            capacity: per_sec,
+            tokens: per_sec,
+            refill_per_sec: per_sec,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes `amount` tokens out of the bucket and returns how long the caller has to wait
+    /// before they are actually available. The bucket may go into debt, which keeps callers
+    /// queued in the order they reserved.
+    fn reserve(&mut self, amount: f64) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        self.tokens -= amount.min(self.capacity);
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.refill_per_sec)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RateLimiterInner {
+    requests: Option<TokenBucket>,
+    compute_units: Option<TokenBucket>,
+}
+
+/// Token bucket rate limiter on both the number of requests and the compute units they cost.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    inner: Arc<Mutex<RateLimiterInner>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: Option<u32>, compute_units_per_second: Option<u32>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RateLimiterInner {
+                requests: requests_per_second.map(TokenBucket::new),
+                compute_units: compute_units_per_second.map(TokenBucket::new),
+            })),
+        }
+    }
+
+    pub async fn acquire(&self, compute_units: u32) {
+        let wait = {
+            let mut inner = self.inner.lock().unwrap();
+            let request_wait = inner
+                .requests
+                .as_mut()
+                .map_or(Duration::ZERO, |bucket| bucket.reserve(1.0));
+            let compute_wait = inner
+                .compute_units
+                .as_mut()
+                .map_or(Duration::ZERO, |bucket| {
+                    bucket.reserve(compute_units as f64)
+                });
+            request_wait.max(compute_wait)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Rate limited access to the L1 provider, shared by the sync services.
+#[derive(Debug, Clone)]
+pub struct L1Fetcher {
+    provider: Provider<Http>,
+    rate_limiter: RateLimiter,
+    config: FetcherConfig,
+}
+
+impl L1Fetcher {
+    pub fn new(provider: Provider<Http>, config: FetcherConfig) -> Self {
+        info!("L1 fetcher config: {:?}", config);
+        let rate_limiter =
+            RateLimiter::new(config.requests_per_second, config.compute_units_per_second);
+        Self {
+            provider,
+            rate_limiter,
+            config,
+        }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.config.concurrency
+    }
+
+    /// Splits `[from, to]` into consecutive, non-overlapping ranges of at most `range_size` blocks.
+    pub fn ranges(&self, from: u64, to: u64) -> Vec<(u64, u64)> {
+        if from > to {
+            return vec![];
+        }
+        (from..=to)
+            .step_by(self.config.range_size as usize)
+            .map(|start| (start, min(start + self.config.range_size - 1, to)))
+            .collect()
+    }
+
+    pub async fn get_block_number(&self) -> Result<u64, ProviderError> {
+        let block_number = self
+            .request(
+                "eth_blockNumber",
+                BLOCK_NUMBER_CU,
+                self.provider.get_block_number(),
+            )
+            .await?;
+        Ok(block_number.as_u64())
+    }
+
+    pub async fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Result<Vec<TransactionReceipt>, ProviderError> {
+        self.request(
+            "eth_getBlockReceipts",
+            GET_BLOCK_RECEIPTS_CU,
+            self.provider.get_block_receipts(block_number),
+        )
+        .await
+    }
+
+    /// Timestamp of an L1 block, in seconds since the Unix epoch. `None` if the node doesn't
+    /// know the block.
+    pub async fn get_block_timestamp(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<u64>, ProviderError> {
+        let block = self
+            .request(
+                "eth_getBlockByNumber",
+                GET_BLOCK_BY_NUMBER_CU,
+                self.provider.get_block(block_number),
+            )
+            .await?;
+        Ok(block.map(|block| block.timestamp.as_u64()))
+    }
+
+    pub async fn get_transaction(
+        &self,
+        tx_hash: H256,
+    ) -> Result<Option<Transaction>, ProviderError> {
+        self.request(
+            "eth_getTransactionByHash",
+            GET_TRANSACTION_CU,
+            self.provider.get_transaction(tx_hash),
+        )
+        .await
+    }
+
+    /// Waits for the rate limiter and runs `request`, recording its count, latency and errors.
+    async fn request<T>(
+        &self,
+        method: &'static str,
+        compute_units: u32,
+        request: impl Future<Output = Result<T, ProviderError>>,
+    ) -> Result<T, ProviderError> {
+        self.rate_limiter.acquire(compute_units).await;
+
+        let started_at = Instant::now();
+        let result = request.await;
+        counter!("scroll_follower_rpc_requests_total", 1, "method" => method);
+        histogram!(
+            "scroll_follower_rpc_request_duration_seconds",
+            started_at.elapsed().as_secs_f64(),
+            "method" => method
+        );
+        if result.is_err() {
+            counter!("scroll_follower_rpc_errors_total", 1, "method" => method);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn reserve_waits_once_the_burst_is_used() {
+        let mut bucket = TokenBucket::new(4);
+        for _ in 0..4 {
+            assert_eq!(bucket.reserve(1.0), Duration::ZERO);
+        }
+        assert_eq!(bucket.reserve(1.0), Duration::from_millis(250));
+        // Callers in debt are queued behind the earlier ones.
+        assert_eq!(bucket.reserve(1.0), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reserve_refills_up_to_capacity() {
+        let mut bucket = TokenBucket::new(4);
+        assert_eq!(bucket.reserve(4.0), Duration::ZERO);
+
+        tokio::time::advance(Duration::from_millis(250)).await;
+        assert_eq!(bucket.reserve(1.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(1.0), Duration::from_millis(250));
+
+        // A long idle period refills the burst, not more.
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(bucket.reserve(4.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(1.0), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reserve_caps_amounts_above_capacity() {
+        let mut bucket = TokenBucket::new(4);
+        assert_eq!(bucket.reserve(500.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(500.0), Duration::from_secs(1));
+    }
+
+    fn fetcher(range_size: u64) -> L1Fetcher {
+        let provider = Provider::<Http>::try_from("http://127.0.0.1:1").unwrap();
+        L1Fetcher::new(
+            provider,
+            FetcherConfig {
+                range_size,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn ranges_of_a_single_block() {
+        assert_eq!(fetcher(10).ranges(5, 5), [(5, 5)]);
+        assert_eq!(fetcher(1).ranges(5, 5), [(5, 5)]);
+    }
+
+    #[test]
+    fn ranges_end_with_a_shorter_range() {
+        assert_eq!(fetcher(10).ranges(0, 24), [(0, 9), (10, 19), (20, 24)]);
+        assert_eq!(fetcher(10).ranges(3, 22), [(3, 12), (13, 22)]);
+        assert!(fetcher(10).ranges(6, 5).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+use std::{env, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use ethers::{
+    providers::{Middleware, Provider, Ws},
+    types::{Filter, H160},
+};
+use futures::StreamExt;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{fetcher::L1Fetcher, status::SyncStatus};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(12);
+const DEFAULT_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct HeadWatcherConfig {
+    /// WebSocket endpoint used for `newHeads` subscriptions, polling only if `None`.
+    pub ws_url: Option<String>,
+    /// Also subscribe to ScrollChain and L1MessageQueue logs, so that blocks carrying
+    /// relevant events trigger a sync even if the `newHeads` notification is delayed.
+    pub subscribe_logs: bool,
+    /// Contracts whose logs are subscribed to when `subscribe_logs` is set.
+    pub log_addresses: Vec<H160>,
+    /// Interval between `eth_blockNumber` calls while polling.
+    pub poll_interval: Duration,
+    /// How long to keep polling after a subscription dropped before reconnecting.
+    pub resubscribe_delay: Duration,
+    /// Number of blocks the services stay behind the L1 head.
+    pub confirmations: u64,
+}
+
+impl HeadWatcherConfig {
+    pub fn from_env() -> Self {
+        let log_addresses = ["L1_SCROLL_CHAIN", "L1_MESSAGE_QUEUE"]
+            .iter()
+            .filter_map(|key| env::var(key).ok())
+            .map(|address| H160::from_str(&address).unwrap())
+            .collect();
+        let poll_interval = env::var("L1_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map_or(DEFAULT_POLL_INTERVAL, Duration::from_secs);
+
+        Self {
+            ws_url: env::var("L1_WS_URL").ok(),
+            subscribe_logs: env::var("L1_WS_SUBSCRIBE_LOGS").map_or(false, |value| value == "true"),
+            log_addresses,
+            poll_interval,
+            resubscribe_delay: DEFAULT_RESUBSCRIBE_DELAY,
+            confirmations: env::var("L1_CONFIRMATIONS")
+                .ok()
+                .and_then(|confirmations| confirmations.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Tracks the L1 head and publishes it to the sync services, either from a WebSocket
+/// subscription or by polling `eth_blockNumber`.
+#[derive(Debug, Clone)]
+pub struct HeadWatcher {
+    fetcher: L1Fetcher,
+    config: HeadWatcherConfig,
+    head_tx: Arc<watch::Sender<u64>>,
+    status: Arc<SyncStatus>,
+}
+
+impl HeadWatcher {
+    pub fn new(
+        fetcher: L1Fetcher,
+        config: HeadWatcherConfig,
+        status: Arc<SyncStatus>,
+    ) -> (Self, watch::Receiver<u64>) {
+        let (head_tx, head_rx) = watch::channel(0);
+        (
+            Self {
+                fetcher,
+                config,
+                head_tx: Arc::new(head_tx),
+                status,
+            },
+            head_rx,
+        )
+    }
+
+    /// Watches the L1 head until `shutdown` is cancelled.
+    pub async fn run(&self, shutdown: CancellationToken) {
+        tokio::select! {
+            _ = self.watch() => {}
+            _ = shutdown.cancelled() => info!("L1 head watcher stopped"),
+        }
+    }
+
+    async fn watch(&self) {
+        let Some(ws_url) = self.config.ws_url.clone() else {
+            info!(
+                "L1 head watcher polling every {:?}",
+                self.config.poll_interval
+            );
+            return self.poll().await;
+        };
+
+        loop {
+            match self.subscribe(&ws_url).await {
+                Ok(()) => warn!("L1 head subscription closed"),
+                Err(err) => warn!("L1 head subscription failed: {}", err),
+            }
+            info!(
+                "Falling back to polling for {:?} before resubscribing",
+                self.config.resubscribe_delay
+            );
+            let _ = tokio::time::timeout(self.config.resubscribe_delay, self.poll()).await;
+        }
+    }
+
+    /// Publishes the confirmed head, `confirmations` blocks behind `head`. Only moves it forward,
+    /// so that a lagging notification never rewinds it.
+    fn publish(&self, head: u64) {
+        let confirmed_head = head.saturating_sub(self.config.confirmations);
+        self.head_tx.send_if_modified(|current| {
+            if confirmed_head > *current {
+                *current = confirmed_head;
+                self.status.set_l1_head(head);
+                self.status.set_l1_confirmed_head(confirmed_head);
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    async fn poll(&self) {
+        loop {
+            match self.fetcher.get_block_number().await {
+                Ok(head) => self.publish(head),
+                Err(err) => warn!("Could not fetch L1 block number: {}", err),
+            }
+            tokio::time::sleep(self.config.poll_interval).await;
+        }
+    }
+
+    /// Runs until the subscription is closed by the remote end.
+    async fn subscribe(&self, ws_url: &str) -> Result<()> {
+        let provider = Provider::<Ws>::connect(ws_url).await?;
+        let mut heads = provider.subscribe_blocks().await?;
+        info!("Subscribed to L1 newHeads");
+
+        let log_filter = Filter::new().address(self.config.log_addresses.clone());
+        let mut logs = if self.config.subscribe_logs {
+            info!("Subscribed to L1 logs of {:?}", self.config.log_addresses);
+            Some(provider.subscribe_logs(&log_filter).await?)
+        } else {
+            None
+        };
+
+        // Publish the current head right away instead of waiting for the next block.
+        self.publish(self.fetcher.get_block_number().await?);
+
+        loop {
+            tokio::select! {
+                head = heads.next() => match head {
+                    Some(block) => {
+                        if let Some(number) = block.number {
+                            self.publish(number.as_u64());
+                        }
+                    }
+                    None => return Ok(()),
+                },
+                Some(log) = async {
+                    match logs.as_mut() {
+                        Some(logs) => logs.next().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    if let Some(number) = log.block_number {
+                        self.publish(number.as_u64());
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::cmp::min;
+
+use ethers::types::H256;
+use futures::{stream, StreamExt};
+use tokio::{sync::watch, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::{
+    fetcher::L1Fetcher, rollup_sync_service::RollupSyncService, sync_service::SyncService,
+    L1MessageQueue::L1MessageQueueEvents, ScrollChain::ScrollChainEvents,
+};
+
+/// Relevant logs of a range of L1 blocks, already split per consumer.
+#[derive(Debug)]
+pub struct L1Range {
+    pub from: u64,
+    /// Last block that was fetched, lower than the requested end if fetching failed midway.
+    pub to: u64,
+    /// Message queue events with the L1 block they were emitted in.
+    pub messages: Vec<(L1MessageQueueEvents, u64)>,
+    /// Rollup events with their transaction hash and the L1 block they were emitted in.
+    pub rollup_events: Vec<(ScrollChainEvents, H256, u64)>,
+}
+
+/// Single L1 ingestion stage: downloads the receipts of every L1 block once and dispatches
+/// the relevant logs to the message queue and rollup consumers.
+#[derive(Debug)]
+pub struct L1Ingestor {
+    fetcher: L1Fetcher,
+    sync_service: SyncService,
+    rollup_sync_service: RollupSyncService,
+}
+
+impl L1Ingestor {
+    pub fn new(
+        fetcher: L1Fetcher,
+        sync_service: SyncService,
+        rollup_sync_service: RollupSyncService,
+    ) -> Self {
+        Self {
+            fetcher,
+            sync_service,
+            rollup_sync_service,
+        }
+    }
+
+    /// Syncs up to the current L1 head and then keeps following it, running an incremental
+    /// sync every time the head watcher reports a new head, until `shutdown` is cancelled.
+    pub async fn start(&self, mut head_rx: watch::Receiver<u64>, shutdown: CancellationToken) {
+        info!("L1 ingestor started");
+        while !shutdown.is_cancelled() {
+            let head = *head_rx.borrow_and_update();
+            self.ingest(head, &shutdown).await;
+
+            tokio::select! {
+                changed = head_rx.changed() => {
+                    if changed.is_err() {
+                        info!("L1 head watcher stopped");
+                        break;
+                    }
+                }
+                _ = shutdown.cancelled() => {}
+            }
+        }
+        info!("L1 ingestor stopped");
+    }
+
+    /// Fetches L1 blocks up to `to`, starting right after the last synced block of the consumer
+    /// that is furthest behind. The other consumer skips the blocks it synced already. Every
+    /// consumer commits each range in its own transaction. Cancellation is only checked
+    /// between ranges, so a range that is being handled is always committed.
+    async fn ingest(&self, to: u64, shutdown: &CancellationToken) {
+        let last_synced_block = min(
+            self.sync_service.last_synced_block(),
+            self.rollup_sync_service.last_synced_block(),
+        );
+        if last_synced_block >= to {
+            return;
+        }
+        let from = last_synced_block + 1;
+        debug!("Ingesting L1 blocks from {} to {}", from, to);
+        let started_at = Instant::now();
+        let mut synced_to = last_synced_block;
+        let (mut messages, mut rollup_events) = (0, 0);
+
+        // Ranges are fetched concurrently, but `buffered` yields them in order so that the
+        // last synced blocks never move past a range that has not been fully processed.
+        let mut fetched_ranges = stream::iter(self.fetcher.ranges(from, to))
+            .map(|(start, end)| async move { (end, self.fetch_range(start, end).await) })
+            .buffered(self.fetcher.concurrency());
+
+        loop {
+            let next = tokio::select! {
+                biased;
+                _ = shutdown.cancelled() => {
+                    info!("Received a message to stop the L1 ingestor");
+                    break;
+                }
+                next = fetched_ranges.next() => next,
+            };
+            let Some((end, range)) = next else {
+                break;
+            };
+            let Some(range) = range else {
+                warn!("Range starting at block 0 could not be fetched, stopping");
+                break;
+            };
+            debug!(
+                "Fetched {} messages and {} rollup events from block {} to {}",
+                range.messages.len(),
+                range.rollup_events.len(),
+                range.from,
+                range.to
+            );
+
+            messages += range.messages.len();
+            rollup_events += range.rollup_events.len();
+
+            self.sync_service.handle_logs(range.messages, range.to);
+            if let Err(err) = self
+                .rollup_sync_service
+                .handle_rollup_events(range.rollup_events, range.to)
+                .await
+            {
+                warn!(
+                    "Error handling the rollup events of blocks {} to {}, stopping: {:#}",
+                    range.from, range.to, err
+                );
+                break;
+            }
+            synced_to = range.to;
+
+            if range.to < end {
+                warn!(
+                    "Range {} to {} was only fetched up to {}, stopping",
+                    range.from, end, range.to
+                );
+                break;
+            }
+        }
+
+        info!(
+            "Synced L1 blocks {} to {}: {} messages, {} rollup events in {:?}",
+            from,
+            synced_to,
+            messages,
+            rollup_events,
+            started_at.elapsed()
+        );
+    }
+
+    /// Fetches the logs of the blocks `from` to `to`, stopping at the first block that fails.
+    /// Returns `None` if not even the first block could be fetched and it is block 0.
+    pub async fn fetch_range(&self, from: u64, to: u64) -> Option<L1Range> {
+        let mut range = L1Range {
+            from,
+            to,
+            messages: vec![],
+            rollup_events: vec![],
+        };
+        debug!("Fetching logs from {} to {}", from, to);
+        for block_number in from..=to {
+            let receipts = match self.fetcher.get_block_receipts(block_number).await {
+                Ok(receipts) => receipts,
+                Err(err) => {
+                    warn!(
+                        "Error fetching receipts for block number {}: {}",
+                        block_number, err
+                    );
+                    range.to = block_number.checked_sub(1)?;
+                    return Some(range);
+                }
+            };
+
+            range.messages.extend(
+                self.sync_service
+                    .filter_logs(&receipts)
+                    .into_iter()
+                    .map(|event| (event, block_number)),
+            );
+            range.rollup_events.extend(
+                self.rollup_sync_service
+                    .filter_rollup_events(&receipts)
+                    .into_iter()
+                    .map(|(event, tx_hash)| (event, tx_hash, block_number)),
+            );
+        }
+        Some(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        sync::{Arc, Mutex},
+    };
+
+    use alloy_sol_types::SolEvent;
+    use ethers::{
+        providers::{Http, Provider},
+        types::{Log, TransactionReceipt, H160},
+    };
+    use jsonrpsee::{core::RpcResult, proc_macros::rpc, server::ServerBuilder};
+
+    use super::*;
+    use crate::{
+        fetcher::FetcherConfig,
+        status::SyncStatus,
+        storage::{MemoryStorage, Storage},
+        test_utils::message,
+        L1MessageQueue::QueueTransaction,
+    };
+
+    const GENESIS_BLOCK: u64 = 19972300;
+    const L1_SCROLL_MESSENGER: &str = "0x6774bcbd5cecef1336b5300fb5186a12ddd8b367";
+    const L1_SCROLL_CHAIN: &str = "0xa13baf47339d63b743e7da8741db5456dac1e556";
+
+    #[rpc(server, namespace = "eth")]
+    trait ReceiptsApi {
+        #[method(name = "getBlockReceipts")]
+        fn block_receipts(&self, number: String) -> RpcResult<Vec<TransactionReceipt>>;
+    }
+
+    /// Serves a single `QueueTransaction` in `message_block` and records the blocks requested.
+    struct ReceiptsRpc {
+        message_block: u64,
+        requested: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl ReceiptsApiServer for ReceiptsRpc {
+        fn block_receipts(&self, number: String) -> RpcResult<Vec<TransactionReceipt>> {
+            let block_number = u64::from_str_radix(number.trim_start_matches("0x"), 16).unwrap();
+            self.requested.lock().unwrap().push(block_number);
+            if block_number != self.message_block {
+                return Ok(vec![]);
+            }
+            let message = message(0);
+            let log = QueueTransaction {
+                sender: message.sender,
+                target: message.to,
+                value: message.value,
+                queueIndex: message.queue_index,
+                gasLimit: message.gas,
+                data: message.data,
+            }
+            .encode_log_data();
+            Ok(vec![TransactionReceipt {
+                to: Some(L1_SCROLL_MESSENGER.parse().unwrap()),
+                logs: vec![Log {
+                    address: H160::zero(),
+                    topics: log
+                        .topics()
+                        .iter()
+                        .map(|topic| H256::from(topic.0))
+                        .collect(),
+                    data: log.data.to_vec().into(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn second_pass_starts_after_the_synced_block() {
+        env::set_var("L1_SCROLL_MESSENGER", L1_SCROLL_MESSENGER);
+        env::set_var("L1_SCROLL_CHAIN", L1_SCROLL_CHAIN);
+        let requested = Arc::new(Mutex::new(vec![]));
+        let server = ServerBuilder::default().build("127.0.0.1:0").await.unwrap();
+        let provider_url = format!("http://{}", server.local_addr().unwrap());
+        let handle = server.start(
+            ReceiptsRpc {
+                message_block: GENESIS_BLOCK + 10,
+                requested: requested.clone(),
+            }
+            .into_rpc(),
+        );
+
+        let provider = Provider::<Http>::try_from(provider_url.as_str()).unwrap();
+        let fetcher = L1Fetcher::new(
+            provider,
+            FetcherConfig {
+                range_size: 4,
+                ..Default::default()
+            },
+        );
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let ingestor = L1Ingestor::new(
+            fetcher.clone(),
+            SyncService::new(storage.clone(), status.clone()),
+            RollupSyncService::new(storage.clone(), fetcher, status.clone()),
+        );
+        let shutdown = CancellationToken::new();
+
+        ingestor.ingest(GENESIS_BLOCK + 10, &shutdown).await;
+        let mut blocks = requested.lock().unwrap().split_off(0);
+        blocks.sort();
+        assert_eq!(
+            blocks,
+            (GENESIS_BLOCK + 1..=GENESIS_BLOCK + 10).collect::<Vec<_>>()
+        );
+        assert_eq!(status.l1_messages_indexed(), 1);
+        assert_eq!(status.l1_message_synced_block(), GENESIS_BLOCK + 10);
+        assert_eq!(status.rollup_synced_block(), GENESIS_BLOCK + 10);
+
+        // The block of the message ended the first pass, it is not fetched nor counted again.
+        ingestor.ingest(GENESIS_BLOCK + 20, &shutdown).await;
+        let mut blocks = requested.lock().unwrap().split_off(0);
+        blocks.sort();
+        assert_eq!(
+            blocks,
+            (GENESIS_BLOCK + 11..=GENESIS_BLOCK + 20).collect::<Vec<_>>()
+        );
+        assert_eq!(status.l1_messages_indexed(), 1);
+        assert_eq!(status.batches_indexed(), 0);
+        assert_eq!(status.l1_message_synced_block(), GENESIS_BLOCK + 20);
+        assert_eq!(status.rollup_synced_block(), GENESIS_BLOCK + 20);
+        assert_eq!(storage.cursors().unwrap().last_l1_message_index, Some(0));
+        handle.stop().unwrap();
+    }
+}
@@ -0,0 +1,25 @@
+use std::env;
+
+use tracing_subscriber::EnvFilter;
+
+/// Used when `RUST_LOG` is not set: our own modules at `info`, dependencies at `warn`.
+const DEFAULT_FILTER: &str = "warn,scroll_reth=info";
+
+/// Installs the global tracing subscriber.
+///
+/// Levels are controlled through `RUST_LOG`, which accepts per-module targets such as
+/// `scroll_reth::l1_ingestor=debug`. Setting `LOG_FORMAT=json` switches to JSON lines. Logs go
+/// to stderr so that the output of the CLI commands can be piped.
+pub fn init_logging() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(true)
+        .with_writer(std::io::stderr);
+
+    match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().init(),
+        _ => builder.init(),
+    }
+}
@@ -1,86 +1,235 @@
-use std::{env, path::Path, sync::Arc};
+use std::{env, path::Path, sync::Arc, time::Duration};
 
 use alloy_sol_types::sol;
 use anyhow::Result;
+use clap::Parser;
+use cli::{Cli, Command};
+use db::{init_db, open_db_read_only};
+use db_command::run_db_command;
 use ethers::providers::Provider;
-use reth_db::{
-    mdbx::DatabaseArguments,
-    models::client_version::ClientVersion,
-    test_utils::{ERROR_DB_CREATION, ERROR_TABLE_CREATION},
-    DatabaseEnv, DatabaseEnvKind,
-};
+use export::run_export;
+use fetcher::{FetcherConfig, L1Fetcher};
+use head_watcher::{HeadWatcher, HeadWatcherConfig};
+use l1_ingestor::L1Ingestor;
+use logging::init_logging;
+use metrics_server::{install_recorder, MetricsServer, MetricsServerConfig};
+use progress::ProgressReporter;
+use pruner::{PruneConfig, Pruner};
+use rewind::run_rewind;
 use rollup_sync_service::RollupSyncService;
-
+use rpc::{run_rpc_server, RpcServerConfig, ScrollRpc};
+use shutdown::wait_for_signal;
+use snapshot::{run_import, run_snapshot};
+use storage::{MdbxStorage, MemoryStorage, Storage};
+
+mod batch_status;
+mod cli;
+mod db;
+mod db_command;
+mod export;
+mod fetcher;
+mod head_watcher;
+mod l1_ingestor;
+mod logging;
+mod metrics_server;
+mod migrations;
+mod progress;
+mod pruner;
+mod rewind;
 mod rollup_sync_service;
 mod rollup_sync_service_util;
+mod rpc;
+mod shutdown;
+mod snapshot;
+mod status;
+mod storage;
+mod supervisor;
 mod sync_service;
+mod tables;
+#[cfg(test)]
+mod test_utils;
+mod validation;
+mod withdraw_trie;
 
 sol!(L1MessageQueue, "l1_message_queue.json");
 sol!(ScrollChain, "scroll_chain_abi.json");
+use status::SyncStatus;
+use supervisor::{Supervisor, SupervisorConfig};
 use sync_service::SyncService;
-use tokio::{signal::ctrl_c, sync::oneshot};
-use tracing::info;
-
-fn create_test_db(kind: DatabaseEnvKind, path: &Path) -> Arc<DatabaseEnv> {
-    if !path.exists() {
-        info!("Database does not exist, creating new one...");
-        let env = DatabaseEnv::open(path, kind, DatabaseArguments::new(ClientVersion::default()))
-            .expect(ERROR_DB_CREATION);
-        env.create_tables().expect(ERROR_TABLE_CREATION);
-        Arc::new(env)
-    } else {
-        info!("Opening existing database...");
-        Arc::new(
-            DatabaseEnv::open(path, kind, DatabaseArguments::new(ClientVersion::default()))
-                .expect("Could not open database at path"),
-        )
-    }
-}
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use validation::run_verify;
+use withdraw_trie::run_withdrawal_proof;
+
+/// How often a read-only RPC server picks up the cursors written by the syncing node.
+const READER_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv::dotenv().ok();
+    init_logging();
+    let cli = Cli::parse();
+    let path = env::current_dir()?.join(&cli.datadir);
+
+    match cli.command.unwrap_or(Command::Node { in_memory: false }) {
+        Command::Node { in_memory } => run_node(&path, in_memory).await,
+        Command::Rpc => run_rpc_reader(&path).await,
+        Command::Db { command } => run_db_command(&path, command),
+        Command::Export(args) => run_export(&path, args),
+        Command::Snapshot { output } => run_snapshot(&path, &output),
+        Command::Import { input } => run_import(&path, &input),
+        Command::Rewind { to_l1_block } => run_rewind(&path, to_l1_block),
+        Command::Verify => run_verify(&path),
+        Command::WithdrawalProof(args) => run_withdrawal_proof(&path, args),
+    }
+}
 
-    // Opening database at a specific path
-    let path = env::current_dir().unwrap().join("scroll-db");
-    let db = create_test_db(DatabaseEnvKind::RW, path.as_path());
+async fn run_node(path: &Path, in_memory: bool) -> Result<()> {
+    let prometheus_handle = install_recorder();
+    let storage: Arc<dyn Storage> = if in_memory {
+        info!("Keeping the synced data in memory, it is lost on exit");
+        Arc::new(MemoryStorage::default())
+    } else {
+        Arc::new(MdbxStorage::new(init_db(path)?))
+    };
 
     let rpc_url = env::var("L1_RPC_URL").unwrap();
 
     let provider = Provider::try_from(rpc_url).unwrap();
-
-    let (l1_tx, l1_rx) = oneshot::channel();
-
-    let sync_service = SyncService::new(db.clone(), provider.clone());
-
-    let sync_handle = tokio::spawn(async move {
-        sync_service.start(l1_rx).await;
+    let fetcher = L1Fetcher::new(provider, FetcherConfig::from_env());
+
+    let status = Arc::new(SyncStatus::default());
+    // Cancelled on Ctrl-C or SIGTERM, every task stops at its next safe point.
+    let shutdown = CancellationToken::new();
+
+    let mut supervisor = Supervisor::new(SupervisorConfig::from_env(), shutdown.clone());
+    let health = supervisor.health();
+
+    let (head_watcher, head_rx) = HeadWatcher::new(
+        fetcher.clone(),
+        HeadWatcherConfig::from_env(),
+        status.clone(),
+    );
+    let task_shutdown = shutdown.clone();
+    supervisor.spawn("l1-head-watcher", move || {
+        let head_watcher = head_watcher.clone();
+        let shutdown = task_shutdown.clone();
+        async move {
+            head_watcher.run(shutdown).await;
+            Ok(())
+        }
     });
 
-    sync_handle.await.expect("Sync service task panicked");
+    // The services are rebuilt on every restart so that they pick up their cursors from the
+    // database instead of any state left behind by the failed run.
+    let task_shutdown = shutdown.clone();
+    let task_status = status.clone();
+    let task_storage = storage.clone();
+    supervisor.spawn("l1-ingestor", move || {
+        let storage = task_storage.clone();
+        let fetcher = fetcher.clone();
+        let status = task_status.clone();
+        let head_rx = head_rx.clone();
+        let shutdown = task_shutdown.clone();
+        async move {
+            let sync_service = SyncService::new(storage.clone(), status.clone());
+            let rollup_sync_service = RollupSyncService::new(storage, fetcher.clone(), status);
+            let l1_ingestor = L1Ingestor::new(fetcher, sync_service, rollup_sync_service);
+            l1_ingestor.start(head_rx, shutdown).await;
+            Ok(())
+        }
+    });
 
-    info!("Sync service has been gracefully shut down.");
+    let metrics_server = MetricsServer::new(
+        MetricsServerConfig::from_env(),
+        prometheus_handle,
+        status.clone(),
+        health.clone(),
+        path.to_path_buf(),
+    );
+    let task_shutdown = shutdown.clone();
+    supervisor.spawn("metrics-server", move || {
+        metrics_server.clone().run(task_shutdown.clone())
+    });
 
-    // Now start the rollup sync service in background
-    info!("Rollup sync service starting...");
+    let prune_config = PruneConfig::from_env();
+    if prune_config.is_enabled() {
+        let pruner = Pruner::new(storage.clone(), prune_config);
+        let task_shutdown = shutdown.clone();
+        supervisor.spawn("pruner", move || pruner.clone().run(task_shutdown.clone()));
+    }
 
-    let rollup_sync_service = RollupSyncService::new(db.clone(), provider.clone());
+    let rpc = ScrollRpc::new(storage, status.clone());
+    let rpc_config = RpcServerConfig::from_env();
+    let task_shutdown = shutdown.clone();
+    supervisor.spawn("rpc-server", move || {
+        run_rpc_server(rpc_config.clone(), rpc.clone(), task_shutdown.clone())
+    });
 
-    let (rollup_tx, rollup_rx) = oneshot::channel();
+    let progress_reporter = ProgressReporter::new(status.clone());
+    let task_shutdown = shutdown.clone();
+    supervisor.spawn("progress-reporter", move || {
+        let progress_reporter = progress_reporter.clone();
+        let shutdown = task_shutdown.clone();
+        async move {
+            progress_reporter.run(shutdown).await;
+            Ok(())
+        }
+    });
 
-    let rollup_handle = tokio::spawn(async move {
-        rollup_sync_service.start(rollup_rx).await;
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Termination signal received. Shutting down.");
+        signal_shutdown.cancel();
     });
 
-    // To prevent the main function from exiting immediately, you can wait for a signal or sleep
-    tokio::select! {
-        _ = rollup_handle => {
-            info!("Rollup sync service has been gracefully shut down.");
-        }
-        _ = ctrl_c() => {
-            info!("Termination signal received. Shutting down.");
-            let _ = rollup_tx.send(());
-            let _ = l1_tx.send(());
-        }
+    let result = supervisor.wait().await;
+    status.report();
+    for (name, state) in health.snapshot() {
+        info!(
+            "Task {}: {:?}, restarts: {}, consecutive failures: {}, last error: {:?}",
+            name, state.health, state.restarts, state.consecutive_failures, state.last_error
+        );
     }
+    result?;
+    info!("All services have been gracefully shut down.");
     Ok(())
 }
+
+/// Serves JSON-RPC from a read-only view of a database that another process is syncing, so
+/// that RPC can be scaled out or restarted independently of the node.
+async fn run_rpc_reader(path: &Path) -> Result<()> {
+    let storage: Arc<dyn Storage> = Arc::new(MdbxStorage::new(open_db_read_only(path)?));
+    let status = Arc::new(SyncStatus::default());
+    let shutdown = CancellationToken::new();
+
+    // The L1 head is only known to the syncing node, the synced blocks are read back from the
+    // cursors it commits.
+    let reader_storage = storage.clone();
+    let reader_status = status.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(READER_STATUS_INTERVAL);
+        loop {
+            interval.tick().await;
+            match reader_storage.cursors() {
+                Ok(cursors) => reader_status.set_from_cursors(&cursors),
+                Err(err) => warn!("Could not read sync cursors: {}", err),
+            }
+        }
+    });
+
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Termination signal received. Shutting down.");
+        signal_shutdown.cancel();
+    });
+
+    run_rpc_server(
+        RpcServerConfig::from_env(),
+        ScrollRpc::new(storage, status),
+        shutdown,
+    )
+    .await
+}
@@ -0,0 +1,264 @@
+use std::{convert::Infallible, env, fs, net::SocketAddr, path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use metrics::{describe_counter, describe_gauge, describe_histogram, gauge, Unit};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::{
+    status::SyncStatus,
+    supervisor::{HealthRegistry, TaskHealth},
+};
+
+const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9001";
+const DEFAULT_READY_MAX_LAG_BLOCKS: u64 = 20;
+
+/// Installs the global Prometheus recorder and describes the follower's metrics.
+/// Must be called once, before any metric is recorded.
+pub fn install_recorder() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .install_recorder()
+        .expect("Could not install Prometheus recorder");
+
+    describe_gauge!(
+        "scroll_follower_l1_head",
+        "Latest L1 block number reported by the head watcher"
+    );
+    describe_gauge!(
+        "scroll_follower_last_synced_l1_block",
+        "Last L1 block synced by each service"
+    );
+    describe_gauge!(
+        "scroll_follower_sync_lag_blocks",
+        "Number of L1 blocks each service is behind the confirmed head"
+    );
+    describe_counter!(
+        "scroll_follower_l1_messages_indexed_total",
+        "L1 messages stored in the database"
+    );
+    describe_counter!(
+        "scroll_follower_batches_total",
+        "Batch events processed, by event type"
+    );
+    describe_counter!(
+        "scroll_follower_range_violations_total",
+        "Gaps and overlaps in the L2 block ranges of committed batches, by kind"
+    );
+    describe_counter!(
+        "scroll_follower_l1_message_violations_total",
+        "Committed batches whose popped L1 messages don't match the synced queue, by kind"
+    );
+    describe_counter!(
+        "scroll_follower_rpc_requests_total",
+        "L1 RPC requests, by method"
+    );
+    describe_counter!(
+        "scroll_follower_rpc_errors_total",
+        "Failed L1 RPC requests, by method"
+    );
+    describe_histogram!(
+        "scroll_follower_rpc_request_duration_seconds",
+        Unit::Seconds,
+        "Latency of L1 RPC requests, by method"
+    );
+    describe_gauge!(
+        "scroll_follower_sync_blocks_per_second",
+        "L1 blocks synced per second by each service"
+    );
+    describe_gauge!(
+        "scroll_follower_sync_progress_ratio",
+        "Progress of each service towards the L1 head since startup, from 0 to 1"
+    );
+    describe_gauge!(
+        "scroll_follower_sync_eta_seconds",
+        Unit::Seconds,
+        "Estimated time until each service reaches the L1 head, 0 if unknown or synced"
+    );
+    describe_gauge!(
+        "scroll_follower_db_size_bytes",
+        Unit::Bytes,
+        "Size of the MDBX data file"
+    );
+
+    handle
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsServerConfig {
+    pub addr: SocketAddr,
+    /// Maximum number of blocks a service may lag behind the confirmed L1 head while still
+    /// being ready.
+    pub ready_max_lag_blocks: u64,
+}
+
+impl MetricsServerConfig {
+    pub fn from_env() -> Self {
+        let addr = env::var("METRICS_ADDR").unwrap_or_else(|_| DEFAULT_METRICS_ADDR.to_string());
+        Self {
+            addr: addr.parse().expect("Invalid METRICS_ADDR"),
+            ready_max_lag_blocks: env::var("READY_MAX_LAG_BLOCKS")
+                .ok()
+                .and_then(|lag| lag.parse().ok())
+                .unwrap_or(DEFAULT_READY_MAX_LAG_BLOCKS),
+        }
+    }
+}
+
+/// Serves `/metrics` in the Prometheus text format, along with the `/health` (liveness) and
+/// `/ready` (readiness) probes used for orchestration.
+#[derive(Debug, Clone)]
+pub struct MetricsServer {
+    config: MetricsServerConfig,
+    handle: PrometheusHandle,
+    status: Arc<SyncStatus>,
+    health: HealthRegistry,
+    db_path: PathBuf,
+}
+
+impl MetricsServer {
+    pub fn new(
+        config: MetricsServerConfig,
+        handle: PrometheusHandle,
+        status: Arc<SyncStatus>,
+        health: HealthRegistry,
+        db_path: PathBuf,
+    ) -> Self {
+        Self {
+            config,
+            handle,
+            status,
+            health,
+            db_path,
+        }
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
+        let addr = self.config.addr;
+        let server = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let server = server.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request)) }
+                }))
+            }
+        });
+
+        info!("Metrics server listening on {}", addr);
+        Server::try_bind(&addr)?
+            .serve(make_service)
+            .with_graceful_shutdown(async move { shutdown.cancelled().await })
+            .await?;
+        Ok(())
+    }
+
+    fn handle(&self, request: Request<Body>) -> Response<Body> {
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, "/metrics") => {
+                self.record_gauges();
+                Response::new(Body::from(self.handle.render()))
+            }
+            (&Method::GET, "/health") => self.health(),
+            (&Method::GET, "/ready") => self.ready(),
+            _ => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .unwrap(),
+        }
+    }
+
+    /// Live as long as no supervised task has exhausted its restarts.
+    fn health(&self) -> Response<Body> {
+        let tasks = self.health.snapshot();
+        let healthy = tasks
+            .values()
+            .all(|state| state.health != TaskHealth::Failed);
+        let body = json!({
+            "healthy": healthy,
+            "tasks": tasks
+                .iter()
+                .map(|(name, state)| {
+                    (
+                        name.to_string(),
+                        json!({
+                            "health": format!("{:?}", state.health),
+                            "restarts": state.restarts,
+                            "lastError": state.last_error,
+                        }),
+                    )
+                })
+                .collect::<serde_json::Map<_, _>>(),
+        });
+        json_response(healthy, body)
+    }
+
+    /// Ready once the L1 head is known and both services are within `ready_max_lag_blocks` of
+    /// the confirmed head, which is as far as they sync.
+    fn ready(&self) -> Response<Body> {
+        let l1_head = self.status.l1_head();
+        let confirmed_head = self.status.l1_confirmed_head();
+        let l1_message_lag = confirmed_head.saturating_sub(self.status.l1_message_synced_block());
+        let rollup_lag = confirmed_head.saturating_sub(self.status.rollup_synced_block());
+        let ready = confirmed_head > 0
+            && l1_message_lag <= self.config.ready_max_lag_blocks
+            && rollup_lag <= self.config.ready_max_lag_blocks;
+        let body = json!({
+            "ready": ready,
+            "l1Head": l1_head,
+            "l1ConfirmedHead": confirmed_head,
+            "l1MessageLagBlocks": l1_message_lag,
+            "rollupLagBlocks": rollup_lag,
+            "maxLagBlocks": self.config.ready_max_lag_blocks,
+        });
+        json_response(ready, body)
+    }
+
+    /// Gauges are derived from the shared sync status at scrape time.
+    fn record_gauges(&self) {
+        let l1_head = self.status.l1_head();
+        gauge!("scroll_follower_l1_head", l1_head as f64);
+        let confirmed_head = self.status.l1_confirmed_head();
+
+        let services = [
+            ("l1_messages", self.status.l1_message_synced_block()),
+            ("rollup", self.status.rollup_synced_block()),
+        ];
+        for (service, synced_block) in services {
+            gauge!(
+                "scroll_follower_last_synced_l1_block",
+                synced_block as f64,
+                "service" => service
+            );
+            gauge!(
+                "scroll_follower_sync_lag_blocks",
+                confirmed_head.saturating_sub(synced_block) as f64,
+                "service" => service
+            );
+        }
+
+        if let Ok(metadata) = fs::metadata(self.db_path.join("mdbx.dat")) {
+            gauge!("scroll_follower_db_size_bytes", metadata.len() as f64);
+        }
+    }
+}
+
+fn json_response(ok: bool, body: serde_json::Value) -> Response<Body> {
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
@@ -0,0 +1,630 @@
+use anyhow::{bail, Context, Result};
+use ethers::utils::rlp;
+use reth_db::{
+    cursor::DbCursorRO,
+    database::Database,
+    table::Table,
+    transaction::{DbTx, DbTxMut},
+    DatabaseEnv,
+};
+use tracing::info;
+
+use crate::{
+    db::{
+        get_schema_version, FIRST_COMMITTED_BATCH_INDEX_KEY, LAST_COMMITTED_BATCH_INDEX_KEY,
+        LAST_L1_MESSAGE_INDEX_KEY, SCHEMA_VERSION_KEY,
+    },
+    rollup_sync_service_util::ChunkBlockRange,
+    sync_service::L1MessageTx,
+    tables::{
+        BatchBlockRanges, BatchBlocks, BatchChunkRanges, ChunkRanges, L1MessageBlocks, L1Messages,
+        Metadata,
+    },
+};
+
+type RwTx = <DatabaseEnv as Database>::TXMut;
+
+/// Upgrades a database from `version - 1` to `version`.
+struct Migration {
+    version: u64,
+    description: &'static str,
+    run: fn(&RwTx) -> Result<()>,
+}
+
+/// Every change to a key or value format gets a migration here, and `SCHEMA_VERSION` is bumped
+/// to its version. Databases without a version record are at version 0.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "backfill the last L1 message index and the committed batch bounds",
+        run: backfill_index_bounds,
+    },
+    Migration {
+        version: 2,
+        description: "move the data from the String keyed tables to the typed follower tables",
+        run: move_to_typed_tables,
+    },
+    Migration {
+        version: 3,
+        description: "backfill the L2 block range of every batch",
+        run: backfill_batch_block_ranges,
+    },
+];
+
+/// Schema version written by this build.
+pub const SCHEMA_VERSION: u64 = 3;
+
+/// Brings the database to `SCHEMA_VERSION`. Each migration commits together with the version
+/// it upgrades to, so an interrupted upgrade continues with the migration that didn't finish.
+/// New databases are created at the current version and skip the migrations.
+pub fn migrate(db: &DatabaseEnv, created: bool) -> Result<()> {
+    if created {
+        let tx = db.tx_mut()?;
+        tx.put::<Metadata>(SCHEMA_VERSION_KEY.to_string(), SCHEMA_VERSION)?;
+        tx.commit()?;
+        return Ok(());
+    }
+
+    let version = stored_schema_version(&db.tx()?)?;
+    if version > SCHEMA_VERSION {
+        bail!(
+            "Database schema version {} is newer than the supported version {}",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > version)
+    {
+        info!(
+            "Migrating database to schema version {}: {}",
+            migration.version, migration.description
+        );
+        let tx = db.tx_mut()?;
+        (migration.run)(&tx)
+            .with_context(|| format!("Migration to schema version {} failed", migration.version))?;
+        tx.put::<Metadata>(SCHEMA_VERSION_KEY.to_string(), migration.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Read-only handles can't migrate, so they refuse any database that isn't at the current
+/// schema version.
+pub fn check_schema_version(db: &DatabaseEnv) -> Result<()> {
+    let version = stored_schema_version(&db.tx()?)?;
+    if version < SCHEMA_VERSION {
+        bail!(
+            "Database schema version {} is outdated, start the node once to migrate it to version {}",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    if version > SCHEMA_VERSION {
+        bail!(
+            "Database schema version {} is newer than the supported version {}",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Before version 2 the schema version was kept in the legacy `SyncL1LastBlockNumber` table.
+/// Read-only handles can't create tables, so `Metadata` is missing from databases that were
+/// never opened by a build with the typed tables.
+fn stored_schema_version<TX: DbTx>(tx: &TX) -> Result<u64> {
+    if table_exists::<Metadata>(tx) {
+        if let Some(version) = get_schema_version(tx)? {
+            return Ok(version);
+        }
+    }
+    if !table_exists::<legacy::SyncL1LastBlockNumber>(tx) {
+        return Ok(0);
+    }
+    Ok(tx
+        .get::<legacy::SyncL1LastBlockNumber>(SCHEMA_VERSION_KEY.to_string())?
+        .unwrap_or_default())
+}
+
+/// Opening a cursor fails if the table was never created.
+fn table_exists<T: Table>(tx: &impl DbTx) -> bool {
+    tx.cursor_read::<T>().is_ok()
+}
+
+/// Tables of the reth-db fork the follower used to store its data in, with String keys such
+/// as `L1{queue index}` and RLP encoded values. Only migrations use them.
+mod legacy {
+    use reth_db::table::Table;
+
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SyncL1LastBlockNumber;
+
+    impl Table for SyncL1LastBlockNumber {
+        const NAME: &'static str = "SyncL1LastBlockNumber";
+        type Key = String;
+        type Value = u64;
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SyncL1MessageQueue;
+
+    impl Table for SyncL1MessageQueue {
+        const NAME: &'static str = "SyncL1MessageQueue";
+        type Key = String;
+        type Value = Vec<u8>;
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct RollupSyncL1LastBlockNumber;
+
+    impl Table for RollupSyncL1LastBlockNumber {
+        const NAME: &'static str = "RollupSyncL1LastBlockNumber";
+        type Key = String;
+        type Value = u64;
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct RollupSyncL1BatchChunkRanges;
+
+    impl Table for RollupSyncL1BatchChunkRanges {
+        const NAME: &'static str = "RollupSyncL1BatchChunkRanges";
+        type Key = String;
+        type Value = Vec<u8>;
+    }
+
+    pub fn parse_l1_message_key(key: &str) -> Option<u64> {
+        key.strip_prefix("L1")?.parse().ok()
+    }
+
+    pub fn parse_l1_message_block_key(key: &str) -> Option<u64> {
+        key.strip_prefix("L1Block")?.parse().ok()
+    }
+
+    pub fn parse_batch_chunk_ranges_key(key: &str) -> Option<u64> {
+        key.strip_prefix("R-bcr")?.parse().ok()
+    }
+
+    pub fn parse_batch_l1_block_key(key: &str) -> Option<u64> {
+        key.strip_prefix("R-blk")?.parse().ok()
+    }
+}
+
+/// Databases synced before the index keys were maintained lack the last L1 message index and
+/// the committed batch bounds, which the RPC lookups rely on. They are derived from the stored
+/// entries.
+fn backfill_index_bounds(tx: &RwTx) -> Result<()> {
+    if !table_exists::<legacy::SyncL1MessageQueue>(tx)
+        || !table_exists::<legacy::RollupSyncL1BatchChunkRanges>(tx)
+    {
+        return Ok(());
+    }
+
+    let mut last_message_index = None;
+    let mut cursor = tx.cursor_read::<legacy::SyncL1MessageQueue>()?;
+    for entry in cursor.walk(None)? {
+        let (key, _) = entry?;
+        last_message_index = last_message_index.max(legacy::parse_l1_message_key(&key));
+    }
+
+    let mut batch_bounds: Option<(u64, u64)> = None;
+    let mut cursor = tx.cursor_read::<legacy::RollupSyncL1BatchChunkRanges>()?;
+    for entry in cursor.walk(None)? {
+        let (key, _) = entry?;
+        if let Some(batch_index) = legacy::parse_batch_chunk_ranges_key(&key) {
+            batch_bounds = Some(
+                batch_bounds.map_or((batch_index, batch_index), |(first, last)| {
+                    (first.min(batch_index), last.max(batch_index))
+                }),
+            );
+        }
+    }
+
+    if let Some(last_message_index) = last_message_index {
+        tx.put::<legacy::SyncL1LastBlockNumber>(
+            LAST_L1_MESSAGE_INDEX_KEY.to_string(),
+            last_message_index,
+        )?;
+    }
+    if let Some((first, last)) = batch_bounds {
+        tx.put::<legacy::RollupSyncL1LastBlockNumber>(
+            FIRST_COMMITTED_BATCH_INDEX_KEY.to_string(),
+            first,
+        )?;
+        tx.put::<legacy::RollupSyncL1LastBlockNumber>(
+            LAST_COMMITTED_BATCH_INDEX_KEY.to_string(),
+            last,
+        )?;
+    }
+    info!(
+        "Backfilled last L1 message index {:?} and committed batch bounds {:?}",
+        last_message_index, batch_bounds
+    );
+    Ok(())
+}
+
+/// Copies every entry of the legacy tables into the typed tables, decoding the index from the
+/// String keys, and empties the legacy tables. Cursors, bounds and the schema version keep
+/// their key in the `Metadata` table.
+fn move_to_typed_tables(tx: &RwTx) -> Result<()> {
+    let (mut messages, mut batches) = (0, 0);
+
+    if table_exists::<legacy::SyncL1LastBlockNumber>(tx) {
+        let mut cursor = tx.cursor_read::<legacy::SyncL1LastBlockNumber>()?;
+        for entry in cursor.walk(None)? {
+            let (key, value) = entry?;
+            match legacy::parse_l1_message_block_key(&key) {
+                Some(queue_index) => tx.put::<L1MessageBlocks>(queue_index, value)?,
+                None => tx.put::<Metadata>(key, value)?,
+            }
+        }
+        tx.clear::<legacy::SyncL1LastBlockNumber>()?;
+    }
+
+    if table_exists::<legacy::SyncL1MessageQueue>(tx) {
+        let mut cursor = tx.cursor_read::<legacy::SyncL1MessageQueue>()?;
+        for entry in cursor.walk(None)? {
+            let (key, encoded) = entry?;
+            let Some(queue_index) = legacy::parse_l1_message_key(&key) else {
+                bail!("Unexpected key {} in SyncL1MessageQueue", key);
+            };
+            let message: L1MessageTx = alloy_rlp::Decodable::decode(&mut encoded.as_slice())
+                .with_context(|| format!("Could not decode L1 message {}", queue_index))?;
+            tx.put::<L1Messages>(queue_index, message)?;
+            messages += 1;
+        }
+        tx.clear::<legacy::SyncL1MessageQueue>()?;
+    }
+
+    if table_exists::<legacy::RollupSyncL1LastBlockNumber>(tx) {
+        let mut cursor = tx.cursor_read::<legacy::RollupSyncL1LastBlockNumber>()?;
+        for entry in cursor.walk(None)? {
+            let (key, value) = entry?;
+            match legacy::parse_batch_l1_block_key(&key) {
+                Some(batch_index) => tx.put::<BatchBlocks>(batch_index, value)?,
+                None => tx.put::<Metadata>(key, value)?,
+            }
+        }
+        tx.clear::<legacy::RollupSyncL1LastBlockNumber>()?;
+    }
+
+    if table_exists::<legacy::RollupSyncL1BatchChunkRanges>(tx) {
+        let mut cursor = tx.cursor_read::<legacy::RollupSyncL1BatchChunkRanges>()?;
+        for entry in cursor.walk(None)? {
+            let (key, encoded) = entry?;
+            let Some(batch_index) = legacy::parse_batch_chunk_ranges_key(&key) else {
+                bail!("Unexpected key {} in RollupSyncL1BatchChunkRanges", key);
+            };
+            let chunks = rlp::Rlp::new(&encoded)
+                .as_list()
+                .with_context(|| format!("Could not decode batch {}", batch_index))?;
+            tx.put::<BatchChunkRanges>(batch_index, ChunkRanges(chunks))?;
+            batches += 1;
+        }
+        tx.clear::<legacy::RollupSyncL1BatchChunkRanges>()?;
+    }
+
+    info!(
+        "Moved {} L1 messages and {} batches to the typed tables",
+        messages, batches
+    );
+    Ok(())
+}
+
+/// Batch lookups by L2 block use the block ranges, which are kept when the chunk ranges are
+/// pruned. They are derived from the chunk ranges of the stored batches.
+fn backfill_batch_block_ranges(tx: &RwTx) -> Result<()> {
+    let mut batches = 0;
+    let mut cursor = tx.cursor_read::<BatchChunkRanges>()?;
+    for entry in cursor.walk(None)? {
+        let (batch_index, ChunkRanges(chunks)) = entry?;
+        if let (Some(first_chunk), Some(last_chunk)) = (chunks.first(), chunks.last()) {
+            let range = ChunkBlockRange {
+                start_block_number: first_chunk.start_block_number,
+                end_block_number: last_chunk.end_block_number,
+            };
+            tx.put::<BatchBlockRanges>(batch_index, range)?;
+            batches += 1;
+        }
+    }
+    info!("Backfilled the block range of {} batches", batches);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use reth_db::{
+        mdbx::{DatabaseArguments, DatabaseFlags},
+        models::client_version::ClientVersion,
+        DatabaseEnvKind,
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        db::{
+            get_batch_block_range, get_committed_batch_bounds, get_last_l1_message_index, init_db,
+            open_db_read_only, LAST_SYNCED_L1_BLOCK_KEY, ROLLUP_LAST_SYNCED_L1_BLOCK_KEY,
+        },
+        tables::TABLES,
+        test_utils::message,
+    };
+
+    /// Opens a database in the layout of the reth-db fork: only the legacy tables, no version
+    /// file and no migrations run.
+    fn open_legacy_db(datadir: &TempDir) -> DatabaseEnv {
+        let db = DatabaseEnv::open(
+            datadir.path(),
+            DatabaseEnvKind::RW,
+            DatabaseArguments::new(ClientVersion::default()),
+        )
+        .unwrap();
+        let tx = db.begin_rw_txn().unwrap();
+        for table in [
+            legacy::SyncL1LastBlockNumber::NAME,
+            legacy::SyncL1MessageQueue::NAME,
+            legacy::RollupSyncL1LastBlockNumber::NAME,
+            legacy::RollupSyncL1BatchChunkRanges::NAME,
+        ] {
+            tx.create_db(Some(table), DatabaseFlags::default()).unwrap();
+        }
+        tx.commit().unwrap();
+        db
+    }
+
+    /// Batch `n` covers the L2 blocks `10n` to `10n + 9` in two chunks.
+    fn chunks(batch_index: u64) -> Vec<ChunkBlockRange> {
+        let start = batch_index * 10;
+        vec![
+            ChunkBlockRange {
+                start_block_number: start,
+                end_block_number: start + 4,
+            },
+            ChunkBlockRange {
+                start_block_number: start + 5,
+                end_block_number: start + 9,
+            },
+        ]
+    }
+
+    /// L1 messages 3 to 5 and batches 10 to 12, keyed and encoded like the fork stored them.
+    fn seed_legacy_db(db: &DatabaseEnv) {
+        let tx = db.tx_mut().unwrap();
+        for queue_index in 3..=5 {
+            tx.put::<legacy::SyncL1MessageQueue>(
+                format!("L1{}", queue_index),
+                alloy_rlp::encode(message(queue_index)),
+            )
+            .unwrap();
+            tx.put::<legacy::SyncL1LastBlockNumber>(
+                format!("L1Block{}", queue_index),
+                100 + queue_index,
+            )
+            .unwrap();
+        }
+        tx.put::<legacy::SyncL1LastBlockNumber>(LAST_SYNCED_L1_BLOCK_KEY.to_string(), 200)
+            .unwrap();
+        for batch_index in 10..=12 {
+            tx.put::<legacy::RollupSyncL1BatchChunkRanges>(
+                format!("R-bcr{}", batch_index),
+                rlp::encode_list(&chunks(batch_index)).to_vec(),
+            )
+            .unwrap();
+            tx.put::<legacy::RollupSyncL1LastBlockNumber>(
+                format!("R-blk{}", batch_index),
+                100 + batch_index,
+            )
+            .unwrap();
+        }
+        tx.put::<legacy::RollupSyncL1LastBlockNumber>(
+            ROLLUP_LAST_SYNCED_L1_BLOCK_KEY.to_string(),
+            200,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+    }
+
+    /// The state migrated from `seed_legacy_db`.
+    fn assert_migrated(db: &DatabaseEnv) {
+        let tx = db.tx().unwrap();
+        assert_eq!(get_schema_version(&tx).unwrap(), Some(SCHEMA_VERSION));
+        assert_eq!(get_last_l1_message_index(&tx).unwrap(), Some(5));
+        assert_eq!(get_committed_batch_bounds(&tx).unwrap(), Some((10, 12)));
+        for batch_index in 10..=12 {
+            assert_eq!(
+                get_batch_block_range(&tx, batch_index).unwrap(),
+                Some(ChunkBlockRange {
+                    start_block_number: batch_index * 10,
+                    end_block_number: batch_index * 10 + 9,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn backfill_index_bounds_from_legacy_tables() {
+        let datadir = TempDir::new().unwrap();
+        let db = open_legacy_db(&datadir);
+        seed_legacy_db(&db);
+
+        let tx = db.tx_mut().unwrap();
+        backfill_index_bounds(&tx).unwrap();
+        assert_eq!(
+            tx.get::<legacy::SyncL1LastBlockNumber>(LAST_L1_MESSAGE_INDEX_KEY.to_string())
+                .unwrap(),
+            Some(5)
+        );
+        assert_eq!(
+            tx.get::<legacy::RollupSyncL1LastBlockNumber>(
+                FIRST_COMMITTED_BATCH_INDEX_KEY.to_string()
+            )
+            .unwrap(),
+            Some(10)
+        );
+        assert_eq!(
+            tx.get::<legacy::RollupSyncL1LastBlockNumber>(
+                LAST_COMMITTED_BATCH_INDEX_KEY.to_string()
+            )
+            .unwrap(),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn migrate_legacy_database() {
+        let datadir = TempDir::new().unwrap();
+        let db = open_legacy_db(&datadir);
+        seed_legacy_db(&db);
+        drop(db);
+
+        let db = init_db(datadir.path()).unwrap();
+        assert_migrated(&db);
+        // Up to date databases are left alone.
+        migrate(&db, false).unwrap();
+        assert_migrated(&db);
+    }
+
+    #[test]
+    fn migrate_resumes_interrupted_upgrade() {
+        let datadir = TempDir::new().unwrap();
+        let db = open_legacy_db(&datadir);
+        seed_legacy_db(&db);
+        // Version 1 committed, the node stopped before version 2 did.
+        let tx = db.tx_mut().unwrap();
+        backfill_index_bounds(&tx).unwrap();
+        tx.put::<legacy::SyncL1LastBlockNumber>(SCHEMA_VERSION_KEY.to_string(), 1)
+            .unwrap();
+        tx.commit().unwrap();
+        assert_eq!(stored_schema_version(&db.tx().unwrap()).unwrap(), 1);
+        drop(db);
+
+        let db = init_db(datadir.path()).unwrap();
+        assert_migrated(&db);
+    }
+
+    #[test]
+    fn read_only_open_refuses_legacy_database() {
+        let datadir = TempDir::new().unwrap();
+        let db = open_legacy_db(&datadir);
+        seed_legacy_db(&db);
+        drop(db);
+
+        // The legacy layout has no Metadata table, which counts as version 0.
+        let err = open_db_read_only(datadir.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Database schema version 0 is outdated, start the node once to migrate it to version {}",
+                SCHEMA_VERSION
+            )
+        );
+
+        init_db(datadir.path()).unwrap();
+        open_db_read_only(datadir.path()).unwrap();
+    }
+
+    #[test]
+    fn migrate_refuses_newer_database() {
+        let datadir = TempDir::new().unwrap();
+        let db = init_db(datadir.path()).unwrap();
+        let tx = db.tx_mut().unwrap();
+        tx.put::<Metadata>(SCHEMA_VERSION_KEY.to_string(), SCHEMA_VERSION + 1)
+            .unwrap();
+        tx.commit().unwrap();
+        drop(db);
+
+        let err = init_db(datadir.path()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("is newer than the supported version"));
+    }
+
+    #[test]
+    fn move_legacy_tables_to_typed_tables() {
+        let datadir = TempDir::new().unwrap();
+        let db = open_legacy_db(&datadir);
+        seed_legacy_db(&db);
+        let tx = db.begin_rw_txn().unwrap();
+        for table in TABLES {
+            tx.create_db(Some(table), DatabaseFlags::default()).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let tx = db.tx_mut().unwrap();
+        move_to_typed_tables(&tx).unwrap();
+        tx.commit().unwrap();
+
+        let tx = db.tx().unwrap();
+        for queue_index in 3..=5 {
+            let stored = tx.get::<L1Messages>(queue_index).unwrap().unwrap();
+            assert_eq!(
+                alloy_rlp::encode(stored),
+                alloy_rlp::encode(message(queue_index))
+            );
+            assert_eq!(
+                tx.get::<L1MessageBlocks>(queue_index).unwrap(),
+                Some(100 + queue_index)
+            );
+        }
+        for batch_index in 10..=12 {
+            assert_eq!(
+                tx.get::<BatchChunkRanges>(batch_index).unwrap().unwrap().0,
+                chunks(batch_index)
+            );
+            assert_eq!(
+                tx.get::<BatchBlocks>(batch_index).unwrap(),
+                Some(100 + batch_index)
+            );
+        }
+        assert_eq!(
+            tx.get::<Metadata>(LAST_SYNCED_L1_BLOCK_KEY.to_string())
+                .unwrap(),
+            Some(200)
+        );
+        assert_eq!(
+            tx.get::<Metadata>(ROLLUP_LAST_SYNCED_L1_BLOCK_KEY.to_string())
+                .unwrap(),
+            Some(200)
+        );
+        assert_eq!(tx.entries::<legacy::SyncL1LastBlockNumber>().unwrap(), 0);
+        assert_eq!(tx.entries::<legacy::SyncL1MessageQueue>().unwrap(), 0);
+        assert_eq!(
+            tx.entries::<legacy::RollupSyncL1LastBlockNumber>().unwrap(),
+            0
+        );
+        assert_eq!(
+            tx.entries::<legacy::RollupSyncL1BatchChunkRanges>()
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn backfill_block_ranges_from_chunk_ranges() {
+        let datadir = TempDir::new().unwrap();
+        let db = init_db(datadir.path()).unwrap();
+        let tx = db.tx_mut().unwrap();
+        for batch_index in 10..=12 {
+            tx.put::<BatchChunkRanges>(batch_index, ChunkRanges(chunks(batch_index)))
+                .unwrap();
+        }
+        // Batches committed without chunks have no block range.
+        tx.put::<BatchChunkRanges>(13, ChunkRanges(vec![])).unwrap();
+        backfill_batch_block_ranges(&tx).unwrap();
+        tx.commit().unwrap();
+
+        let tx = db.tx().unwrap();
+        for batch_index in 10..=12 {
+            assert_eq!(
+                get_batch_block_range(&tx, batch_index).unwrap(),
+                Some(ChunkBlockRange {
+                    start_block_number: batch_index * 10,
+                    end_block_number: batch_index * 10 + 9,
+                })
+            );
+        }
+        assert_eq!(get_batch_block_range(&tx, 13).unwrap(), None);
+        assert_eq!(tx.entries::<BatchBlockRanges>().unwrap(), 3);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{env, sync::Arc, time::Duration};
+
+use metrics::gauge;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::status::SyncStatus;
+
+const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Rates and estimates computed by a sample.
+#[derive(Debug, PartialEq)]
+struct Sample {
+    blocks_per_sec: f64,
+    items_per_sec: f64,
+    /// Share of the blocks between the first sample and the head that were synced, in `[0, 1]`.
+    progress: f64,
+    eta: Option<Duration>,
+}
+
+/// Progress of a single service since it was first seen syncing.
+#[derive(Debug)]
+struct ServiceProgress {
+    name: &'static str,
+    /// Block and time of the first sample, `None` until the service reported its cursor.
+    start: Option<(u64, Instant)>,
+    last_block: u64,
+    last_items: u64,
+}
+
+impl ServiceProgress {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            start: None,
+            last_block: 0,
+            last_items: 0,
+        }
+    }
+
+    /// Logs and records the progress made since the previous sample. The first sample with a
+    /// known cursor only sets the starting point and returns `None`.
+    fn sample(
+        &mut self,
+        synced_block: u64,
+        items: u64,
+        l1_head: u64,
+        interval: Duration,
+    ) -> Option<Sample> {
+        let Some((start_block, started_at)) = self.start else {
+            if synced_block > 0 {
+                self.start = Some((synced_block, Instant::now()));
+                self.last_block = synced_block;
+                self.last_items = items;
+            }
+            return None;
+        };
+
+        let interval_secs = interval.as_secs_f64().max(f64::EPSILON);
+        let blocks_per_sec = synced_block.saturating_sub(self.last_block) as f64 / interval_secs;
+        let items_per_sec = items.saturating_sub(self.last_items) as f64 / interval_secs;
+
+        // Percentage of the distance between where this run started and the current head.
+        let total = l1_head.saturating_sub(start_block);
+        let done = synced_block.saturating_sub(start_block);
+        let progress = if total == 0 {
+            1.0
+        } else {
+            (done as f64 / total as f64).min(1.0)
+        };
+
+        // The ETA uses the average rate of the whole run, which is steadier than the last
+        // interval's rate.
+        let remaining = l1_head.saturating_sub(synced_block);
+        let average_rate = done as f64 / started_at.elapsed().as_secs_f64().max(f64::EPSILON);
+        let eta = (remaining > 0 && average_rate > 0.0)
+            .then(|| Duration::from_secs_f64(remaining as f64 / average_rate));
+
+        if remaining > 0 {
+            info!(
+                "{}: synced L1 block {} of {} ({:.2}%), {:.1} blocks/s, {:.1} items/s, {} items indexed, ETA {}",
+                self.name,
+                synced_block,
+                l1_head,
+                progress * 100.0,
+                blocks_per_sec,
+                items_per_sec,
+                items,
+                eta.map_or("unknown".to_string(), format_duration),
+            );
+        }
+
+        gauge!("scroll_follower_sync_blocks_per_second", blocks_per_sec, "service" => self.name);
+        gauge!("scroll_follower_sync_progress_ratio", progress, "service" => self.name);
+        gauge!(
+            "scroll_follower_sync_eta_seconds",
+            eta.map_or(0.0, |eta| eta.as_secs_f64()),
+            "service" => self.name
+        );
+
+        self.last_block = synced_block;
+        self.last_items = items;
+        Some(Sample {
+            blocks_per_sec,
+            items_per_sec,
+            progress,
+            eta,
+        })
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}h{:02}m{:02}s", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+/// Periodically reports sync speed, percentage to the L1 head and ETA for each service.
+#[derive(Debug, Clone)]
+pub struct ProgressReporter {
+    status: Arc<SyncStatus>,
+    interval: Duration,
+}
+
+impl ProgressReporter {
+    pub fn new(status: Arc<SyncStatus>) -> Self {
+        let interval = env::var("PROGRESS_INTERVAL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map_or(DEFAULT_PROGRESS_INTERVAL, Duration::from_secs);
+        Self { status, interval }
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) {
+        let mut l1_messages = ServiceProgress::new("l1_messages");
+        let mut rollup = ServiceProgress::new("rollup");
+
+        let mut interval = tokio::time::interval(self.interval);
+        let mut last_sample = Instant::now();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => return,
+            }
+
+            // The services only sync up to the confirmed head.
+            let l1_head = self.status.l1_confirmed_head();
+            let elapsed = last_sample.elapsed();
+            last_sample = Instant::now();
+
+            l1_messages.sample(
+                self.status.l1_message_synced_block(),
+                self.status.l1_messages_indexed(),
+                l1_head,
+                elapsed,
+            );
+            rollup.sample(
+                self.status.rollup_synced_block(),
+                self.status.batches_indexed(),
+                l1_head,
+                elapsed,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    #[tokio::test(start_paused = true)]
+    async fn first_sample_sets_the_starting_point() {
+        let mut progress = ServiceProgress::new("test");
+        // Not synced yet, the cursor is unknown.
+        assert_eq!(progress.sample(0, 0, 200, INTERVAL), None);
+        assert!(progress.start.is_none());
+
+        assert_eq!(progress.sample(100, 5, 200, INTERVAL), None);
+        assert_eq!(progress.start.map(|(block, _)| block), Some(100));
+
+        tokio::time::advance(INTERVAL).await;
+        let sample = progress.sample(150, 25, 200, INTERVAL).unwrap();
+        assert_eq!(sample.blocks_per_sec, 5.0);
+        assert_eq!(sample.items_per_sec, 2.0);
+        assert_eq!(sample.progress, 0.5);
+        assert_eq!(sample.eta, Some(Duration::from_secs(10)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_rate_has_no_eta() {
+        let mut progress = ServiceProgress::new("test");
+        progress.sample(100, 5, 200, INTERVAL);
+
+        tokio::time::advance(INTERVAL).await;
+        let sample = progress.sample(100, 5, 200, INTERVAL).unwrap();
+        assert_eq!(sample.blocks_per_sec, 0.0);
+        assert_eq!(sample.items_per_sec, 0.0);
+        assert_eq!(sample.progress, 0.0);
+        assert_eq!(sample.eta, None);
+
+        // An empty interval doesn't divide by zero.
+        let sample = progress.sample(100, 5, 200, Duration::ZERO).unwrap();
+        assert_eq!(sample.blocks_per_sec, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn head_behind_the_synced_block_does_not_underflow() {
+        let mut progress = ServiceProgress::new("test");
+        progress.sample(100, 5, 90, INTERVAL);
+
+        tokio::time::advance(INTERVAL).await;
+        let sample = progress.sample(110, 5, 90, INTERVAL).unwrap();
+        assert_eq!(sample.blocks_per_sec, 1.0);
+        assert_eq!(sample.progress, 1.0);
+        assert_eq!(sample.eta, None);
+    }
+}
@@ -0,0 +1,111 @@
+use std::{env, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use metrics::counter;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::storage::Storage;
+
+const DEFAULT_PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// What is pruned once batches are finalized. Nothing is pruned by default. Pruning keeps the
+/// cursors, the L1 block and L2 block range of every batch and the L1 messages popped per
+/// batch, so lookups by batch index and by L2 block keep working.
+#[derive(Debug, Clone)]
+pub struct PruneConfig {
+    /// Remove the L1 messages popped by the last finalized batch and every batch before it.
+    pub l1_messages: bool,
+    /// Keep the chunk ranges of only this many of the latest finalized batches. Older batches
+    /// are returned without chunks.
+    pub keep_finalized_batches: Option<u64>,
+    pub interval: Duration,
+}
+
+impl PruneConfig {
+    pub fn from_env() -> Self {
+        Self {
+            l1_messages: env::var("PRUNE_L1_MESSAGES").map_or(false, |value| value == "true"),
+            keep_finalized_batches: env::var("PRUNE_KEEP_FINALIZED_BATCHES")
+                .ok()
+                .map(|keep| keep.parse().expect("Invalid PRUNE_KEEP_FINALIZED_BATCHES")),
+            interval: env::var("PRUNE_INTERVAL_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .map_or(DEFAULT_PRUNE_INTERVAL, Duration::from_secs),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.l1_messages || self.keep_finalized_batches.is_some()
+    }
+
+    /// Index of the first batch whose chunk ranges are kept, `None` if batches aren't pruned.
+    pub fn first_kept_batch(&self, last_finalized_batch_index: u64) -> Option<u64> {
+        self.keep_finalized_batches
+            .map(|keep| (last_finalized_batch_index + 1).saturating_sub(keep))
+    }
+}
+
+/// Entries removed by a single prune.
+#[derive(Debug, Default)]
+pub struct PruneStats {
+    pub l1_messages: u64,
+    pub batches: u64,
+}
+
+/// Periodically removes the data the prune config doesn't keep.
+#[derive(Debug, Clone)]
+pub struct Pruner {
+    storage: Arc<dyn Storage>,
+    config: PruneConfig,
+}
+
+impl Pruner {
+    pub fn new(storage: Arc<dyn Storage>, config: PruneConfig) -> Self {
+        Self { storage, config }
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) -> Result<()> {
+        let mut interval = tokio::time::interval(self.config.interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+
+            let stats = self.storage.prune(&self.config)?;
+            if stats.l1_messages > 0 || stats.batches > 0 {
+                info!(
+                    "Pruned {} L1 messages and the chunk ranges of {} batches",
+                    stats.l1_messages, stats.batches
+                );
+            }
+            counter!("scroll_follower_pruned_total", stats.l1_messages, "kind" => "l1_messages");
+            counter!("scroll_follower_pruned_total", stats.batches, "kind" => "batches");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keeping(keep_finalized_batches: Option<u64>) -> PruneConfig {
+        PruneConfig {
+            l1_messages: false,
+            keep_finalized_batches,
+            interval: DEFAULT_PRUNE_INTERVAL,
+        }
+    }
+
+    #[test]
+    fn first_kept_batch() {
+        assert_eq!(keeping(None).first_kept_batch(5), None);
+        assert_eq!(keeping(Some(0)).first_kept_batch(5), Some(6));
+        assert_eq!(keeping(Some(1)).first_kept_batch(5), Some(5));
+        assert_eq!(keeping(Some(3)).first_kept_batch(5), Some(3));
+        assert_eq!(keeping(Some(6)).first_kept_batch(5), Some(0));
+        assert_eq!(keeping(Some(100)).first_kept_batch(5), Some(0));
+    }
+}
@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use reth_db::{
+    cursor::DbCursorRW,
+    database::Database,
+    transaction::{DbTx, DbTxMut},
+};
+use tracing::info;
+
+use crate::{
+    db::{
+        get_batch_block_range, get_batch_l1_block, get_batch_status, get_committed_batch_bounds,
+        get_l1_message, get_l1_message_block, get_last_finalized_batch_index,
+        get_last_l1_message_index, init_db, FIRST_COMMITTED_BATCH_INDEX_KEY,
+        LAST_COMMITTED_BATCH_INDEX_KEY, LAST_FINALIZED_BATCH_INDEX_KEY, LAST_L1_MESSAGE_INDEX_KEY,
+        LAST_SYNCED_L1_BLOCK_KEY, ROLLUP_LAST_SYNCED_L1_BLOCK_KEY,
+    },
+    tables::{
+        BatchBlockRanges, BatchBlocks, BatchChunkRanges, BatchFinalizedRoots,
+        BatchL1MessagesPopped, BatchStatuses, BlockContexts, L1MessageBlocks, L1Messages, Metadata,
+    },
+};
+
+/// Removes the L1 messages and batches that were synced from L1 blocks after `to_l1_block`
+/// and moves both sync cursors back to it, so the node syncs them again on its next start.
+/// Everything happens in a single transaction. The node must not be running.
+pub fn run_rewind(datadir: &Path, to_l1_block: u64) -> Result<()> {
+    if !datadir.join("mdbx.dat").exists() {
+        bail!("No database at {}", datadir.display());
+    }
+    let db = init_db(datadir)?;
+    let tx = db.tx_mut()?;
+
+    let removed_messages = rewind_l1_messages(&tx, to_l1_block)?;
+    let removed_batches = rewind_batches(&tx, to_l1_block)?;
+    tx.commit()?;
+
+    info!(
+        "Rewound to L1 block {}: removed {} L1 messages and {} batches",
+        to_l1_block, removed_messages, removed_batches
+    );
+    Ok(())
+}
+
+/// Messages are stored in queue order, which is also L1 block order, so they are removed from
+/// the last one down until one was emitted at or before `to_l1_block`. Messages below the
+/// lowest stored one were pruned, the walk stops there and the last message index is set to
+/// the one right below it.
+fn rewind_l1_messages<TX: DbTx + DbTxMut>(tx: &TX, to_l1_block: u64) -> Result<u64> {
+    let Some(last_synced_block) = tx.get::<Metadata>(LAST_SYNCED_L1_BLOCK_KEY.to_string())? else {
+        return Ok(0);
+    };
+    if last_synced_block <= to_l1_block {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    let mut last_index = get_last_l1_message_index(tx)?;
+    while let Some(queue_index) = last_index {
+        if get_l1_message(tx, queue_index)?.is_none() {
+            break;
+        }
+        let Some(l1_block) = get_l1_message_block(tx, queue_index)? else {
+            bail!(
+                "L1 message {} was synced before L1 blocks were recorded, it can't be rewound",
+                queue_index
+            );
+        };
+        if l1_block <= to_l1_block {
+            break;
+        }
+        tx.delete::<L1Messages>(queue_index, None)?;
+        tx.delete::<L1MessageBlocks>(queue_index, None)?;
+        removed += 1;
+        last_index = queue_index.checked_sub(1);
+    }
+
+    match last_index {
+        Some(last_index) => {
+            tx.put::<Metadata>(LAST_L1_MESSAGE_INDEX_KEY.to_string(), last_index)?
+        }
+        None => {
+            tx.delete::<Metadata>(LAST_L1_MESSAGE_INDEX_KEY.to_string(), None)?;
+        }
+    }
+    tx.put::<Metadata>(LAST_SYNCED_L1_BLOCK_KEY.to_string(), to_l1_block)?;
+    Ok(removed)
+}
+
+/// Batches are committed in index order, so they are removed from the last committed one down
+/// until one was committed at or before `to_l1_block`, including batches committed without
+/// chunks. Reverted batches are still stored and are removed like any other.
+fn rewind_batches<TX: DbTx + DbTxMut>(tx: &TX, to_l1_block: u64) -> Result<u64> {
+    let Some(last_synced_block) =
+        tx.get::<Metadata>(ROLLUP_LAST_SYNCED_L1_BLOCK_KEY.to_string())?
+    else {
+        return Ok(0);
+    };
+    if last_synced_block <= to_l1_block {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    let mut remaining_bounds = None;
+    if let Some((first, last)) = get_committed_batch_bounds(tx)? {
+        for batch_index in (first..=last).rev() {
+            let range = get_batch_block_range(tx, batch_index)?;
+            let Some(l1_block) = get_batch_l1_block(tx, batch_index)? else {
+                if range.is_none() && get_batch_status(tx, batch_index)?.is_none() {
+                    // Nothing was stored for this index.
+                    continue;
+                }
+                bail!(
+                    "Batch {} was synced before L1 blocks were recorded, it can't be rewound",
+                    batch_index
+                );
+            };
+            if l1_block <= to_l1_block {
+                remaining_bounds = Some((first, batch_index));
+                break;
+            }
+            tx.delete::<BatchChunkRanges>(batch_index, None)?;
+            tx.delete::<BatchBlockRanges>(batch_index, None)?;
+            tx.delete::<BatchL1MessagesPopped>(batch_index, None)?;
+            tx.delete::<BatchBlocks>(batch_index, None)?;
+            tx.delete::<BatchStatuses>(batch_index, None)?;
+            if let Some(range) = range {
+                for l2_block_number in range.start_block_number..=range.end_block_number {
+                    tx.delete::<BlockContexts>(l2_block_number, None)?;
+                }
+            }
+            removed += 1;
+        }
+    }
+
+    match remaining_bounds {
+        Some((first, last)) => {
+            tx.put::<Metadata>(FIRST_COMMITTED_BATCH_INDEX_KEY.to_string(), first)?;
+            tx.put::<Metadata>(LAST_COMMITTED_BATCH_INDEX_KEY.to_string(), last)?;
+        }
+        None => {
+            for key in [
+                FIRST_COMMITTED_BATCH_INDEX_KEY,
+                LAST_COMMITTED_BATCH_INDEX_KEY,
+            ] {
+                tx.delete::<Metadata>(key.to_string(), None)?;
+            }
+        }
+    }
+    let last_finalized = match remaining_bounds {
+        Some((first, last)) => rewind_batch_statuses(tx, first, last, to_l1_block)?,
+        None => None,
+    };
+    match last_finalized {
+        Some(last_finalized) => {
+            tx.put::<Metadata>(LAST_FINALIZED_BATCH_INDEX_KEY.to_string(), last_finalized)?
+        }
+        None => {
+            tx.delete::<Metadata>(LAST_FINALIZED_BATCH_INDEX_KEY.to_string(), None)?;
+        }
+    }
+    // Roots are only stored for finalized batches, the ones after the last finalized batch
+    // came from undone finalizations.
+    let mut cursor = tx.cursor_write::<BatchFinalizedRoots>()?;
+    while let Some((batch_index, _)) = cursor.last()? {
+        if last_finalized.is_some_and(|last_finalized| batch_index <= last_finalized) {
+            break;
+        }
+        cursor.delete_current()?;
+    }
+    tx.put::<Metadata>(ROLLUP_LAST_SYNCED_L1_BLOCK_KEY.to_string(), to_l1_block)?;
+    Ok(removed)
+}
+
+/// Moves the remaining batches that were finalized or reverted after `to_l1_block` back to
+/// committed, and returns the new last finalized batch. Batches are finalized in order, so this
+/// stops at the last batch that was finalized at or before `to_l1_block`.
+fn rewind_batch_statuses<TX: DbTx + DbTxMut>(
+    tx: &TX,
+    first: u64,
+    last: u64,
+    to_l1_block: u64,
+) -> Result<Option<u64>> {
+    for batch_index in (first..=last).rev() {
+        if get_batch_block_range(tx, batch_index)?.is_none() {
+            continue;
+        }
+        let Some(status) = get_batch_status(tx, batch_index)? else {
+            // Synced before the lifecycle was tracked, none of the removed batches can stay
+            // finalized.
+            let last_finalized = get_last_finalized_batch_index(tx)?;
+            return Ok(last_finalized.map(|finalized| finalized.min(batch_index)));
+        };
+        if status
+            .finalized
+            .as_ref()
+            .is_some_and(|finalized| finalized.block_number <= to_l1_block)
+        {
+            return Ok(Some(batch_index));
+        }
+        let mut rewound = status.clone();
+        rewound.rewind(to_l1_block);
+        if rewound != status {
+            tx.put::<BatchStatuses>(batch_index, rewound)?;
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ethers::types::H256;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        batch_status::FinalizedRoots,
+        pruner::PruneConfig,
+        storage::{BatchEvent, MdbxStorage, Storage},
+        test_utils::{chunk, committed, message, provenance},
+    };
+
+    /// Messages 0 to 9 emitted in L1 blocks 100 to 109, of which 0 to 4 were popped by the
+    /// finalized batch 0 and pruned.
+    fn sync_and_prune(datadir: &TempDir) {
+        let storage = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        storage
+            .commit_l1_messages(
+                (0..10)
+                    .map(|queue_index| (message(queue_index), 100 + queue_index))
+                    .collect(),
+                120,
+            )
+            .unwrap();
+        storage
+            .commit_batch_events(
+                vec![
+                    committed(0, vec![chunk(0, 9)], 0, 5, 95),
+                    BatchEvent::Finalized {
+                        batch_index: 0,
+                        roots: FinalizedRoots {
+                            state_root: H256::repeat_byte(1),
+                            withdraw_root: H256::repeat_byte(2),
+                        },
+                        provenance: provenance(96),
+                    },
+                ],
+                120,
+            )
+            .unwrap();
+        let config = PruneConfig {
+            l1_messages: true,
+            keep_finalized_batches: None,
+            interval: Duration::from_secs(600),
+        };
+        assert_eq!(storage.prune(&config).unwrap().l1_messages, 5);
+    }
+
+    #[test]
+    fn rewind_l1_messages_of_pruned_database() {
+        let datadir = TempDir::new().unwrap();
+        sync_and_prune(&datadir);
+
+        run_rewind(datadir.path(), 107).unwrap();
+        let storage = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        let cursors = storage.cursors().unwrap();
+        assert_eq!(cursors.last_l1_message_index, Some(7));
+        assert_eq!(cursors.last_synced_l1_block, Some(107));
+        assert!(storage.l1_message(7).unwrap().is_some());
+        assert!(storage.l1_message(8).unwrap().is_none());
+    }
+
+    #[test]
+    fn rewind_every_stored_l1_message_of_pruned_database() {
+        let datadir = TempDir::new().unwrap();
+        sync_and_prune(&datadir);
+
+        run_rewind(datadir.path(), 101).unwrap();
+        let storage = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        let cursors = storage.cursors().unwrap();
+        // The pruned messages stay synced.
+        assert_eq!(cursors.last_l1_message_index, Some(4));
+        assert_eq!(cursors.last_synced_l1_block, Some(101));
+        assert!(storage.l1_messages_in_range(0, 9).unwrap().is_empty());
+        // The batch was committed and finalized before the rewind target.
+        assert_eq!(cursors.last_finalized_batch_index, Some(0));
+    }
+
+    #[test]
+    fn rewind_batches_without_chunks() {
+        let datadir = TempDir::new().unwrap();
+        let storage = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        storage
+            .commit_batch_events(
+                vec![
+                    committed(0, vec![chunk(0, 9)], 0, 0, 101),
+                    committed(1, vec![chunk(10, 12)], 0, 0, 103),
+                    committed(2, vec![], 0, 0, 105),
+                ],
+                110,
+            )
+            .unwrap();
+        drop(storage);
+
+        // The chunkless batch 2 has no block range but is removed along with batch 1.
+        run_rewind(datadir.path(), 102).unwrap();
+        let storage = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        let cursors = storage.cursors().unwrap();
+        assert_eq!(cursors.first_committed_batch_index, Some(0));
+        assert_eq!(cursors.last_committed_batch_index, Some(0));
+        assert_eq!(cursors.rollup_last_synced_l1_block, Some(102));
+        assert!(storage.batch_status(0).unwrap().is_some());
+        assert!(storage.batch_status(1).unwrap().is_none());
+        assert!(storage.batch_status(2).unwrap().is_none());
+        assert!(storage.block_context(10).unwrap().is_none());
+    }
+}
@@ -1,181 +1,284 @@
-use std::{cmp::min, env, fs::File, io::Read, str::FromStr, sync::Arc};
+use std::{collections::HashMap, env, fs::File, io::Read, str::FromStr, sync::Arc};
 
 use alloy_sol_types::{private::FixedBytes, SolEventInterface};
-use ethers::{abi::Abi, types::H256, utils::rlp};
-use ethers::{
-    providers::{Http, Middleware, Provider},
-    types::H160,
-};
-use reth_db::{
-    database::Database,
-    mdbx::{tx::Tx, RW},
-    tables,
-    transaction::{DbTx, DbTxMut},
-    DatabaseEnv,
-};
-use rlp::RlpStream;
+use anyhow::{anyhow, bail, Context, Result};
+use ethers::types::{TransactionReceipt, H160};
+use ethers::{abi::Abi, types::H256};
+use metrics::counter;
 use serde_json::Value;
-use tokio::sync::oneshot;
-use tracing::info;
+use tracing::{debug, error, info};
 
 use crate::{
-    rollup_sync_service_util::{decode_chunk_block_ranges, ChunkBlockRange},
+    batch_status::{FinalizedRoots, L1Provenance},
+    fetcher::L1Fetcher,
+    rollup_sync_service_util::{decode_commit_batch, DecodedCommitBatch},
+    status::SyncStatus,
+    storage::{BatchEvent, CommittedBatch, Storage},
+    validation::{check_chunks, check_follows, check_parent_popped, check_popped_messages},
     ScrollChain::ScrollChainEvents,
 };
 
 #[derive(Debug)]
 pub struct RollupSyncService {
-    db: Arc<DatabaseEnv>,
-    last_synced_block: Option<u64>,
-    provider: Provider<Http>,
+    storage: Arc<dyn Storage>,
+    fetcher: L1Fetcher,
     scroll_chain_abi: Abi,
+    status: Arc<SyncStatus>,
 }
 
 impl RollupSyncService {
-    pub fn new(db: Arc<DatabaseEnv>, provider: Provider<Http>) -> Self {
-        let tx = db.tx_mut().expect("Could not create transaction");
-
-        let mut last_processed_block = tx
-            .get::<tables::RollupSyncL1LastBlockNumber>(
-                "RLastRollupEventSyncedL1BlockNumber".to_string(),
-            )
-            .expect("Could not fetch last synced block number");
+    pub fn new(storage: Arc<dyn Storage>, fetcher: L1Fetcher, status: Arc<SyncStatus>) -> Self {
+        let last_processed_block = storage
+            .cursors()
+            .expect("Could not fetch last synced block number")
+            .rollup_last_synced_l1_block;
         info!("Last processed block: {:?}", last_processed_block);
-        if last_processed_block.is_none() {
-            last_processed_block = Some(19972300);
-            tx.put::<tables::RollupSyncL1LastBlockNumber>(
-                "RLastRollupEventSyncedL1BlockNumber".to_string(),
-                last_processed_block.unwrap(),
-            )
-            .expect("Could not update last synced block number");
-        }
+        let last_processed_block = match last_processed_block {
+            Some(last_processed_block) => last_processed_block,
+            None => {
+                let genesis_block = 19972300;
+                storage
+                    .commit_batch_events(vec![], genesis_block)
+                    .expect("Could not update last synced block number");
+                genesis_block
+            }
+        };
+        status.set_rollup_synced_block(last_processed_block);
 
         let scroll_chain_abi: Abi = get_abi("scroll_chain_abi.json");
         Self {
-            db,
-            last_synced_block: last_processed_block,
-            provider,
+            storage,
+            fetcher,
             scroll_chain_abi,
+            status,
         }
     }
 
-    pub async fn start(&self, terminate_rx: oneshot::Receiver<()>) {
-        info!("Rollup sync service started");
-        let mut tx = self.db.tx_mut().unwrap();
-        loop {
-            tokio::select! {
-                _ = self.fetch_rollup_events(&mut tx) => {
-                    break;
-                }
-                _ = terminate_rx => {
-                    info!("Received a message to stop the Rollup sync service");
-                    break;
-                }
-            }
+    pub fn last_synced_block(&self) -> u64 {
+        self.storage
+            .cursors()
+            .expect("Could not fetch last synced block number")
+            .rollup_last_synced_l1_block
+            .expect("Last synced block is always set on startup")
+    }
+
+    /// Stores the batches committed in a fetched range and moves the last synced block to
+    /// `last_block`. Ranges we already synced past are ignored and so are the events of blocks
+    /// up to our last synced block, the ingestor refetches them when the message consumer is
+    /// behind. Nothing is stored if the events can't be parsed, so the range is synced again.
+    pub async fn handle_rollup_events(
+        &self,
+        mut rollup_events: Vec<(ScrollChainEvents, H256, u64)>,
+        last_block: u64,
+    ) -> Result<()> {
+        let last_synced_block = self.last_synced_block();
+        if last_block <= last_synced_block {
+            return Ok(());
         }
-        tx.commit().expect("Could not commit transaction");
-        info!("Rollup sync service stopped");
-    }
-
-    pub async fn fetch_rollup_events(&self, tx_mut: &mut Tx<RW>) {
-        let from = self.last_synced_block.unwrap();
-        let to = self.provider.get_block_number().await.unwrap().as_u64();
-        info!("Fetching rollup events from {} to {}", from, to);
-
-        for block in (from..to).step_by(100) {
-            let (filtered_rollup_events, last_block_number) = self
-                .fetch_rollup_events_in_range(block, min(block + 100, to))
-                .await;
-            info!(
-                "Fetched {} rollup events from block {} to {}",
-                filtered_rollup_events.len(),
-                block,
-                last_block_number
-            );
-
-            // self.last_synced_block = Some(last_block_number);
-            tx_mut
-                .put::<tables::RollupSyncL1LastBlockNumber>(
-                    "RLastRollupEventSyncedL1BlockNumber".to_string(),
-                    last_block_number,
-                )
-                .expect("Could not update last synced block number");
-
-            self.parse_and_update_rollup_events(filtered_rollup_events, tx_mut)
-                .await;
+        rollup_events.retain(|(_, _, l1_block_number)| *l1_block_number > last_synced_block);
+        debug!(
+            "Handling {} rollup events up to block {}",
+            rollup_events.len(),
+            last_block
+        );
+
+        // Chunk ranges need extra RPC calls, resolve them before committing so the write
+        // transaction is only held for the duration of the writes.
+        let batch_events = self.parse_rollup_events(rollup_events).await?;
+        let (mut committed, mut finalized, mut reverted) = (0, 0, 0);
+        for event in &batch_events {
+            match event {
+                BatchEvent::Committed(_) => committed += 1,
+                BatchEvent::Finalized { .. } => finalized += 1,
+                BatchEvent::Reverted { .. } => reverted += 1,
+            }
         }
+        self.check_block_ranges(&batch_events)?;
+        self.check_l1_messages(&batch_events)?;
+
+        self.storage
+            .commit_batch_events(batch_events, last_block)
+            .expect("Could not commit batches");
+        self.status.set_rollup_synced_block(last_block);
+        self.status.add_batches_indexed(committed);
+        counter!("scroll_follower_batches_total", committed, "event" => "committed");
+        counter!("scroll_follower_batches_total", finalized, "event" => "finalized");
+        counter!("scroll_follower_batches_total", reverted, "event" => "reverted");
+        Ok(())
     }
 
-    pub async fn fetch_rollup_events_in_range(
-        &self,
-        from: u64,
-        to: u64,
-    ) -> (Vec<(ScrollChainEvents, H256)>, u64) {
-        let mut filtered_rollup_events = vec![];
-        let l1_scroll_chain_address = env::var("L1_SCROLL_CHAIN").unwrap();
-        info!("Fetching logs from {} to {}", from, to);
-        for block_number in (from..to + 1).step_by(1) {
-            info!("Block number: {:?}\n", block_number);
-            let receipts = self.provider.get_block_receipts(block_number).await;
-            if receipts.is_err() {
-                info!(
-                    "Error fetching receipts for block number: {:?}",
-                    block_number
+    /// Reports committed batches whose chunks aren't contiguous or that don't start right after
+    /// the previous batch. They are stored anyway, so `verify` and lookups can find them. Fails
+    /// only if the previous batch can't be read.
+    fn check_block_ranges(&self, batch_events: &[BatchEvent]) -> Result<()> {
+        // The previous batch is either committed in the same range or already stored.
+        let mut last_blocks = HashMap::new();
+        for event in batch_events {
+            let BatchEvent::Committed(batch) = event else {
+                continue;
+            };
+            let (Some(first_chunk), Some(last_chunk)) = (batch.chunks.first(), batch.chunks.last())
+            else {
+                continue;
+            };
+            let mut violations = check_chunks(batch.batch_index, &batch.chunks);
+            let previous_last_block = match batch.batch_index.checked_sub(1) {
+                Some(previous_index) => match last_blocks.get(&previous_index) {
+                    Some(last_block) => Some(*last_block),
+                    None => self
+                        .storage
+                        .batch(previous_index)
+                        .context("Could not read previous batch")?
+                        .map(|previous| previous.last_block),
+                },
+                None => None,
+            };
+            if let Some(previous_last_block) = previous_last_block {
+                violations.extend(check_follows(
+                    batch.batch_index,
+                    first_chunk.start_block_number,
+                    previous_last_block,
+                ));
+            }
+            for violation in violations {
+                error!("Block range violation: {}", violation);
+                counter!(
+                    "scroll_follower_range_violations_total",
+                    1,
+                    "kind" => violation.kind.as_str()
                 );
+            }
+            last_blocks.insert(batch.batch_index, last_chunk.end_block_number);
+        }
+        Ok(())
+    }
 
-                return (filtered_rollup_events, block_number - 1);
+    /// Reports committed batches whose parent header doesn't match the messages popped by the
+    /// previous batch, or that pop L1 messages the L1 message sync doesn't have. Messages are
+    /// stored before the rollup events of the same range, so a batch can only pop messages that
+    /// were synced already. Messages below the first stored one were queued before the genesis
+    /// block or pruned, they can't be checked. Fails only if the storage can't be read.
+    fn check_l1_messages(&self, batch_events: &[BatchEvent]) -> Result<()> {
+        // Like `verify`, if no message is left they were all popped by finalized batches.
+        let first_stored_message = match self.storage.first_l1_message_index()? {
+            Some(queue_index) => queue_index,
+            None => match self.storage.cursors()?.last_finalized_batch_index {
+                Some(last_finalized) => self
+                    .storage
+                    .batch_total_l1_messages_popped(last_finalized)?
+                    .unwrap_or(0),
+                None => 0,
+            },
+        };
+        let mut totals = HashMap::new();
+        for event in batch_events {
+            let BatchEvent::Committed(batch) = event else {
+                continue;
+            };
+            let mut violations = vec![];
+            let previous_total = match batch.batch_index.checked_sub(1) {
+                Some(previous_index) => match totals.get(&previous_index) {
+                    Some(total) => Some(*total),
+                    None => self
+                        .storage
+                        .batch_total_l1_messages_popped(previous_index)
+                        .context("Could not read previous batch")?,
+                },
+                None => None,
+            };
+            if let Some(previous_total) = previous_total {
+                violations.extend(check_parent_popped(
+                    batch.batch_index,
+                    batch.parent_total_l1_messages_popped,
+                    previous_total,
+                ));
             }
-            let receipts = receipts.unwrap();
-
-            info!(
-                "block number: {:?},, receipts length: {:?}",
-                block_number,
-                receipts.len()
-            );
-
-            let new_logs: Vec<(ScrollChainEvents, H256)> = receipts
-                .iter()
-                .filter(|receipt| {
-                    receipt.to == Some(H160::from_str(&l1_scroll_chain_address).unwrap())
-                })
-                .flat_map(|receipt| receipt.logs.iter().map(move |log| (receipt, log)))
-                .filter_map(|(receipt, log)| {
-                    let topics: Vec<_> = log
-                        .topics
-                        .iter()
-                        .map(|topic| FixedBytes::new(topic.to_fixed_bytes()))
-                        .collect();
-                    ScrollChainEvents::decode_raw_log(&topics, &log.data, true)
-                        .ok()
-                        .map(|event| (event, receipt.transaction_hash))
-                })
-                .filter(|(event, _)| match event {
-                    ScrollChainEvents::CommitBatch(_) => true,
-                    ScrollChainEvents::RevertBatch(_) => true,
-                    ScrollChainEvents::FinalizeBatch(_) => true,
-                    _ => false,
-                })
-                .collect();
-
-            if new_logs.len() > 0 {
-                info!(
-                    "New logs: {:?}, block_number {:?}",
-                    new_logs.len(),
-                    block_number
+
+            let to = batch.total_l1_messages_popped;
+            let from = batch
+                .parent_total_l1_messages_popped
+                .max(first_stored_message);
+            if from < to {
+                let stored = self.storage.l1_messages_count_in_range(from, to - 1)?;
+                violations.extend(check_popped_messages(batch.batch_index, from, to, stored));
+            }
+
+            for violation in violations {
+                error!("L1 message violation: {}", violation);
+                counter!(
+                    "scroll_follower_l1_message_violations_total",
+                    1,
+                    "kind" => violation.kind()
                 );
-                filtered_rollup_events.extend(new_logs);
             }
+            totals.insert(batch.batch_index, batch.total_l1_messages_popped);
         }
+        Ok(())
+    }
 
-        return (filtered_rollup_events, to);
+    /// Extracts the commit, revert and finalize events from the receipts of a single block.
+    pub fn filter_rollup_events(
+        &self,
+        receipts: &[TransactionReceipt],
+    ) -> Vec<(ScrollChainEvents, H256)> {
+        let l1_scroll_chain_address = env::var("L1_SCROLL_CHAIN").unwrap();
+        receipts
+            .iter()
+            .filter(|receipt| receipt.to == Some(H160::from_str(&l1_scroll_chain_address).unwrap()))
+            .flat_map(|receipt| receipt.logs.iter().map(move |log| (receipt, log)))
+            .filter_map(|(receipt, log)| {
+                let topics: Vec<_> = log
+                    .topics
+                    .iter()
+                    .map(|topic| FixedBytes::new(topic.to_fixed_bytes()))
+                    .collect();
+                ScrollChainEvents::decode_raw_log(&topics, &log.data, true)
+                    .ok()
+                    .map(|event| (event, receipt.transaction_hash))
+            })
+            .filter(|(event, _)| match event {
+                ScrollChainEvents::CommitBatch(_) => true,
+                ScrollChainEvents::RevertBatch(_) => true,
+                ScrollChainEvents::FinalizeBatch(_) => true,
+                _ => false,
+            })
+            .collect()
     }
 
-    pub async fn parse_and_update_rollup_events(
+    /// Turns the rollup events into batch events, decoding every committed batch from its
+    /// commit transaction and recording where on L1 each event happened. Fails if the L1 block
+    /// of an event or a commit transaction can't be fetched.
+    pub async fn parse_rollup_events(
         &self,
-        rollup_events: Vec<(ScrollChainEvents, H256)>,
-        tx_mut: &mut Tx<RW>,
-    ) {
-        for (event, tx_hash) in rollup_events {
+        rollup_events: Vec<(ScrollChainEvents, H256, u64)>,
+    ) -> Result<Vec<BatchEvent>> {
+        let mut batch_events = vec![];
+        // Several events are often emitted in the same L1 block.
+        let mut block_timestamps = HashMap::new();
+        for (event, tx_hash, l1_block_number) in rollup_events {
+            let timestamp = match block_timestamps.get(&l1_block_number) {
+                Some(timestamp) => *timestamp,
+                None => {
+                    let Some(timestamp) = self
+                        .fetcher
+                        .get_block_timestamp(l1_block_number)
+                        .await
+                        .with_context(|| {
+                        format!("Could not fetch L1 block {}", l1_block_number)
+                    })?
+                    else {
+                        bail!("L1 block {} not found", l1_block_number);
+                    };
+                    block_timestamps.insert(l1_block_number, timestamp);
+                    timestamp
+                }
+            };
+            let provenance = L1Provenance {
+                block_number: l1_block_number,
+                tx_hash,
+                timestamp,
+            };
+
             match event {
                 ScrollChainEvents::CommitBatch(commit_batch) => {
                     let batch_index = commit_batch.batchIndex;
@@ -183,44 +286,56 @@ impl RollupSyncService {
                     // convert batch_index to u64
                     let batch_index = batch_index.to::<u64>();
 
-                    let chunk_block_ranges = self.get_chunk_ranges(tx_hash).await;
-
-                    info!(
-                        "Length of chunk_block_ranges: {:?}",
-                        chunk_block_ranges.len()
-                    );
-                    // encode chunk_block_ranges
-                    let mut stream = RlpStream::new_list(chunk_block_ranges.len());
-                    for chunk_range in chunk_block_ranges {
-                        stream.append(&chunk_range);
-                    }
-
-                    let encoded_chunk_block_ranges = stream.out();
-                    tx_mut
-                        .put::<tables::RollupSyncL1BatchChunkRanges>(
-                            format!("R-bcr{}", batch_index),
-                            encoded_chunk_block_ranges.to_vec(),
-                        )
-                        .unwrap();
+                    let decoded = self.get_commit_batch(tx_hash).await?;
+
+                    debug!("Length of chunk_block_ranges: {:?}", decoded.chunks.len());
+                    batch_events.push(BatchEvent::Committed(CommittedBatch {
+                        batch_index,
+                        chunks: decoded.chunks,
+                        blocks: decoded.blocks,
+                        parent_total_l1_messages_popped: decoded.parent_total_l1_messages_popped,
+                        total_l1_messages_popped: decoded.total_l1_messages_popped,
+                        provenance,
+                    }));
+                }
+                ScrollChainEvents::FinalizeBatch(finalize_batch) => {
+                    batch_events.push(BatchEvent::Finalized {
+                        batch_index: finalize_batch.batchIndex.to::<u64>(),
+                        roots: FinalizedRoots {
+                            state_root: H256::from(finalize_batch.stateRoot.0),
+                            withdraw_root: H256::from(finalize_batch.withdrawRoot.0),
+                        },
+                        provenance,
+                    });
+                }
+                ScrollChainEvents::RevertBatch(revert_batch) => {
+                    batch_events.push(BatchEvent::Reverted {
+                        batch_index: revert_batch.batchIndex.to::<u64>(),
+                        provenance,
+                    });
                 }
 
                 _ => {}
             }
         }
+        Ok(batch_events)
     }
 
-    pub async fn get_chunk_ranges(&self, tx_hash: H256) -> Vec<ChunkBlockRange> {
-        let transaction = self
-            .provider
+    /// Decodes the batch from the calldata of its commit transaction. Fails if the transaction
+    /// can't be fetched or decoded.
+    pub async fn get_commit_batch(&self, tx_hash: H256) -> Result<DecodedCommitBatch> {
+        let Some(transaction) = self
+            .fetcher
             .get_transaction(tx_hash)
             .await
-            .unwrap()
-            .unwrap();
+            .with_context(|| format!("Could not fetch commit transaction {:?}", tx_hash))?
+        else {
+            bail!("Commit transaction {:?} not found", tx_hash);
+        };
 
         let tx_data = transaction.input.to_vec();
-        let abi = self.scroll_chain_abi.clone();
-        let chunk_block_ranges = decode_chunk_block_ranges(tx_data, &abi).unwrap();
-        chunk_block_ranges
+        decode_commit_batch(tx_data, &self.scroll_chain_abi)
+            .map_err(|err| anyhow!("Could not decode commit transaction {:?}: {}", tx_hash, err))
     }
 }
 
@@ -236,3 +351,132 @@ pub fn get_abi(path: &str) -> Abi {
     // Parse the ABI
     serde_json::from_value(abi_array).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::U256;
+    use ethers::providers::{Http, Provider};
+    use jsonrpsee::{core::RpcResult, proc_macros::rpc, server::ServerBuilder};
+
+    use super::*;
+    use crate::{fetcher::FetcherConfig, storage::MemoryStorage, ScrollChain::RevertBatch};
+
+    const GENESIS_BLOCK: u64 = 19972300;
+
+    /// Nothing listens on this port, every request fails.
+    const UNREACHABLE_PROVIDER: &str = "http://127.0.0.1:1";
+
+    /// Answers every block request with `null`, like a node that doesn't have the block.
+    #[rpc(server, namespace = "eth")]
+    trait NullBlockApi {
+        #[method(name = "getBlockByNumber")]
+        fn block_by_number(&self, number: String, full: bool) -> RpcResult<Option<u64>>;
+    }
+
+    struct NullBlockRpc;
+
+    impl NullBlockApiServer for NullBlockRpc {
+        fn block_by_number(&self, _number: String, _full: bool) -> RpcResult<Option<u64>> {
+            Ok(None)
+        }
+    }
+
+    fn rollup_sync_service(
+        storage: Arc<dyn Storage>,
+        status: Arc<SyncStatus>,
+        provider_url: &str,
+    ) -> RollupSyncService {
+        let provider = Provider::<Http>::try_from(provider_url).unwrap();
+        let fetcher = L1Fetcher::new(provider, FetcherConfig::default());
+        RollupSyncService::new(storage, fetcher, status)
+    }
+
+    fn revert_batch(batch_index: u64) -> ScrollChainEvents {
+        ScrollChainEvents::RevertBatch(RevertBatch {
+            batchIndex: U256::from(batch_index),
+            batchHash: FixedBytes::ZERO,
+        })
+    }
+
+    #[tokio::test]
+    async fn moves_cursor_over_ranges_without_events() {
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let rollup_sync_service =
+            rollup_sync_service(storage.clone(), status.clone(), UNREACHABLE_PROVIDER);
+        assert_eq!(rollup_sync_service.last_synced_block(), GENESIS_BLOCK);
+        assert_eq!(status.rollup_synced_block(), GENESIS_BLOCK);
+
+        rollup_sync_service
+            .handle_rollup_events(vec![], GENESIS_BLOCK + 100)
+            .await
+            .unwrap();
+        assert_eq!(rollup_sync_service.last_synced_block(), GENESIS_BLOCK + 100);
+        assert_eq!(status.rollup_synced_block(), GENESIS_BLOCK + 100);
+
+        // Ranges that were synced already are ignored.
+        rollup_sync_service
+            .handle_rollup_events(vec![], GENESIS_BLOCK + 50)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.cursors().unwrap().rollup_last_synced_l1_block,
+            Some(GENESIS_BLOCK + 100)
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_events_of_synced_blocks() {
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let rollup_sync_service =
+            rollup_sync_service(storage.clone(), status.clone(), UNREACHABLE_PROVIDER);
+
+        // The event was handled with the range ending at the genesis block. Handling it again
+        // would fetch its L1 block, which fails.
+        let events = vec![(revert_batch(1), H256::zero(), GENESIS_BLOCK)];
+        rollup_sync_service
+            .handle_rollup_events(events, GENESIS_BLOCK + 100)
+            .await
+            .unwrap();
+        assert_eq!(rollup_sync_service.last_synced_block(), GENESIS_BLOCK + 100);
+        assert_eq!(status.batches_indexed(), 0);
+    }
+
+    #[tokio::test]
+    async fn keeps_cursor_when_block_fetch_fails() {
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let rollup_sync_service =
+            rollup_sync_service(storage.clone(), status.clone(), UNREACHABLE_PROVIDER);
+
+        let events = vec![(revert_batch(1), H256::zero(), GENESIS_BLOCK + 1)];
+        let err = rollup_sync_service
+            .handle_rollup_events(events, GENESIS_BLOCK + 100)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Could not fetch L1 block"));
+        assert_eq!(rollup_sync_service.last_synced_block(), GENESIS_BLOCK);
+        assert_eq!(status.rollup_synced_block(), GENESIS_BLOCK);
+    }
+
+    #[tokio::test]
+    async fn keeps_cursor_when_block_is_missing() {
+        let server = ServerBuilder::default().build("127.0.0.1:0").await.unwrap();
+        let provider_url = format!("http://{}", server.local_addr().unwrap());
+        let handle = server.start(NullBlockRpc.into_rpc());
+
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let rollup_sync_service = rollup_sync_service(storage.clone(), status, &provider_url);
+
+        let events = vec![(revert_batch(1), H256::zero(), GENESIS_BLOCK + 1)];
+        let err = rollup_sync_service
+            .handle_rollup_events(events, GENESIS_BLOCK + 100)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        assert_eq!(rollup_sync_service.last_synced_block(), GENESIS_BLOCK);
+        handle.stop().unwrap();
+    }
+}
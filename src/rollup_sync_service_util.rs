@@ -1,13 +1,16 @@
 use ethers::abi::{Abi, Function};
+use ethers::types::U256;
 use ethers::utils::rlp;
 use rlp::{Decodable, Encodable, Rlp, RlpStream};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
-use tracing::info;
+use tracing::debug;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ChunkBlockRange {
-    start_block_number: u64,
-    end_block_number: u64,
+    pub start_block_number: u64,
+    pub end_block_number: u64,
 }
 
 impl Encodable for ChunkBlockRange {
@@ -43,83 +46,137 @@ impl CodecVersion {
     }
 }
 
+/// Size of a block context in an encoded chunk: block number (8), timestamp (8), base fee (32),
+/// gas limit (8), number of transactions (2) and number of L1 messages (2).
+const BLOCK_CONTEXT_LENGTH: usize = 60;
+
+/// An L2 block as attested on L1 by the chunk that contains it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockContext {
+    pub number: u64,
+    pub timestamp: u64,
+    /// Zero for blocks before base fees were introduced on L2.
+    pub base_fee: U256,
+    pub gas_limit: u64,
+    /// Transactions in the block, L1 messages included.
+    pub num_transactions: u16,
+    /// L1 messages popped by the block, skipped ones included.
+    pub num_l1_messages: u16,
+}
+
+impl BlockContext {
+    /// Codec versions 0 and 1 share the same block context layout.
+    fn decode(
+        codec_version: &CodecVersion,
+        context: &[u8; BLOCK_CONTEXT_LENGTH],
+    ) -> Result<Self, Box<dyn Error>> {
+        match codec_version {
+            CodecVersion::CodecV0 | CodecVersion::CodecV1 => Ok(BlockContext {
+                number: u64::from_be_bytes(context[0..8].try_into()?),
+                timestamp: u64::from_be_bytes(context[8..16].try_into()?),
+                base_fee: U256::from_big_endian(&context[16..48]),
+                gas_limit: u64::from_be_bytes(context[48..56].try_into()?),
+                num_transactions: u16::from_be_bytes(context[56..58].try_into()?),
+                num_l1_messages: u16::from_be_bytes(context[58..60].try_into()?),
+            }),
+        }
+    }
+}
+
+impl Encodable for BlockContext {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(6);
+        stream.append(&self.number);
+        stream.append(&self.timestamp);
+        stream.append(&self.base_fee);
+        stream.append(&self.gas_limit);
+        stream.append(&self.num_transactions);
+        stream.append(&self.num_l1_messages);
+    }
+}
+
+impl Decodable for BlockContext {
+    fn decode(rlp: &Rlp) -> Result<Self, rlp::DecoderError> {
+        Ok(BlockContext {
+            number: rlp.val_at(0)?,
+            timestamp: rlp.val_at(1)?,
+            base_fee: rlp.val_at(2)?,
+            gas_limit: rlp.val_at(3)?,
+            num_transactions: rlp.val_at(4)?,
+            num_l1_messages: rlp.val_at(5)?,
+        })
+    }
+}
+
+/// Offset of `totalL1MessagePopped` in a batch header, after the version (1), the batch index
+/// (8) and `l1MessagePopped` (8).
+const TOTAL_L1_MESSAGE_POPPED_OFFSET: usize = 17;
+
+/// What the follower keeps of a `commitBatch` call.
+#[derive(Debug, Clone)]
+pub struct DecodedCommitBatch {
+    pub chunks: Vec<ChunkBlockRange>,
+    /// Every block of the batch, in order.
+    pub blocks: Vec<BlockContext>,
+    /// `totalL1MessagePopped` of the parent batch header, the queue index of the first message
+    /// this batch pops.
+    pub parent_total_l1_messages_popped: u64,
+    /// L1 messages popped by this batch and every batch before it, which is also the queue
+    /// index of the first message the next batch pops.
+    pub total_l1_messages_popped: u64,
+}
+
+/// Block ranges of the chunks and the contexts of all their blocks.
 pub fn decode_block_ranges_from_encoded_chunks(
     codec_version: CodecVersion,
     chunks: Vec<Vec<u8>>,
-) -> Result<Vec<ChunkBlockRange>, Box<dyn Error>> {
+) -> Result<(Vec<ChunkBlockRange>, Vec<BlockContext>), Box<dyn Error>> {
     let mut chunk_block_ranges = Vec::new();
+    let mut blocks = Vec::new();
     for chunk in chunks {
         if chunk.len() < 1 {
             return Err("invalid chunk, length is less than 1".into());
         }
 
         let num_blocks = chunk[0] as usize;
+        let expected_length = 1 + num_blocks * BLOCK_CONTEXT_LENGTH;
 
-        match codec_version {
-            CodecVersion::CodecV0 => {
-                if chunk.len() < 1 + num_blocks * 60 {
-                    return Err(format!(
-                        "invalid chunk byte length, expected: {}, got: {}",
-                        1 + num_blocks * 60,
-                        chunk.len()
-                    )
-                    .into());
-                }
-
-                let mut da_blocks = Vec::new();
-                for i in 0..num_blocks {
-                    let start_idx = 1 + i * 60; // add 1 to skip numBlocks byte
-                    let end_idx = start_idx + 60;
-                    let da_block = chunk[start_idx..end_idx].to_vec();
-                    da_blocks.push(da_block);
-                }
-
-                let start_block_number = u64::from_be_bytes(da_blocks[0][0..8].try_into()?);
-                let end_block_number =
-                    u64::from_be_bytes(da_blocks[num_blocks - 1][0..8].try_into()?);
-
-                chunk_block_ranges.push(ChunkBlockRange {
-                    start_block_number,
-                    end_block_number,
-                });
-            }
-            CodecVersion::CodecV1 => {
-                if chunk.len() != 1 + num_blocks * 60 {
-                    return Err(format!(
-                        "invalid chunk byte length, expected: {}, got: {}",
-                        1 + num_blocks * 60,
-                        chunk.len()
-                    )
-                    .into());
-                }
-
-                let mut da_blocks = Vec::new();
-                info!("******Num of blocks: {:?}\n\n", num_blocks);
-                for i in 0..num_blocks {
-                    let start_idx = 1 + i * 60; // add 1 to skip numBlocks byte
-                    let end_idx = start_idx + 60;
-                    let da_block = chunk[start_idx..end_idx].to_vec();
-                    da_blocks.push(da_block);
-                }
-
-                let start_block_number = u64::from_be_bytes(da_blocks[0][0..8].try_into()?);
-                let end_block_number =
-                    u64::from_be_bytes(da_blocks[num_blocks - 1][0..8].try_into()?);
-
-                chunk_block_ranges.push(ChunkBlockRange {
-                    start_block_number,
-                    end_block_number,
-                });
-            }
+        let valid_length = match codec_version {
+            CodecVersion::CodecV0 => chunk.len() >= expected_length,
+            CodecVersion::CodecV1 => chunk.len() == expected_length,
+        };
+        if !valid_length {
+            return Err(format!(
+                "invalid chunk byte length, expected: {}, got: {}",
+                expected_length,
+                chunk.len()
+            )
+            .into());
         }
+        if num_blocks == 0 {
+            return Err("invalid chunk, it has no blocks".into());
+        }
+
+        debug!("Number of blocks in chunk: {}", num_blocks);
+        let da_blocks = chunk[1..expected_length]
+            .chunks_exact(BLOCK_CONTEXT_LENGTH)
+            .map(|context| BlockContext::decode(&codec_version, context.try_into()?))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        chunk_block_ranges.push(ChunkBlockRange {
+            start_block_number: da_blocks[0].number,
+            end_block_number: da_blocks[num_blocks - 1].number,
+        });
+        blocks.extend(da_blocks);
     }
-    Ok(chunk_block_ranges)
+    Ok((chunk_block_ranges, blocks))
 }
 
-pub fn decode_chunk_block_ranges(
+pub fn decode_commit_batch(
     tx_data: Vec<u8>,
     abi: &Abi,
-) -> Result<Vec<ChunkBlockRange>, Box<dyn Error>> {
+) -> Result<DecodedCommitBatch, Box<dyn Error>> {
     const METHOD_ID_LENGTH: usize = 4;
 
     if tx_data.len() < METHOD_ID_LENGTH {
@@ -147,6 +204,7 @@ pub fn decode_chunk_block_ranges(
 
     let inputs = method.decode_input(&tx_data[METHOD_ID_LENGTH..])?;
     let version: u8 = inputs[0].clone().into_uint().unwrap().as_u64() as u8;
+    let parent_batch_header = inputs[1].clone().into_bytes().unwrap();
     let chunks: Vec<Vec<u8>> = inputs[2]
         .clone()
         .into_array()
@@ -157,5 +215,114 @@ pub fn decode_chunk_block_ranges(
 
     let codec_version = CodecVersion::from_u8(version)?;
 
-    decode_block_ranges_from_encoded_chunks(codec_version, chunks)
+    let parent_total_popped = parent_batch_header
+        .get(TOTAL_L1_MESSAGE_POPPED_OFFSET..TOTAL_L1_MESSAGE_POPPED_OFFSET + 8)
+        .ok_or("parent batch header is too short")?;
+    let parent_total_popped = u64::from_be_bytes(parent_total_popped.try_into()?);
+
+    let (chunks, blocks) = decode_block_ranges_from_encoded_chunks(codec_version, chunks)?;
+    let l1_messages_popped: u64 = blocks
+        .iter()
+        .map(|block| block.num_l1_messages as u64)
+        .sum();
+    Ok(DecodedCommitBatch {
+        chunks,
+        blocks,
+        parent_total_l1_messages_popped: parent_total_popped,
+        total_l1_messages_popped: parent_total_popped + l1_messages_popped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::utils::hex;
+
+    use super::*;
+
+    /// Block 2 of the `blockTrace_02.json` fixture of scroll-tech/da-codec, as its codec v0
+    /// tests encode it.
+    const BLOCK_2_CONTEXT: &str = concat!(
+        "0000000000000002",
+        "0000000063807b2a",
+        "0000000000000000000000000000000000000000000000000000000000001de9",
+        "000355418d1e8184",
+        "0002",
+        "0000",
+    );
+
+    fn block_2() -> BlockContext {
+        BlockContext {
+            number: 2,
+            timestamp: 0x63807b2a,
+            base_fee: U256::from(0x1de9),
+            gas_limit: 0x355418d1e8184,
+            num_transactions: 2,
+            num_l1_messages: 0,
+        }
+    }
+
+    #[test]
+    fn decode_chunk_fixture() {
+        let mut chunk = vec![1];
+        chunk.extend(hex::decode(BLOCK_2_CONTEXT).unwrap());
+        let (ranges, blocks) =
+            decode_block_ranges_from_encoded_chunks(CodecVersion::CodecV1, vec![chunk.clone()])
+                .unwrap();
+        assert_eq!(
+            ranges,
+            [ChunkBlockRange {
+                start_block_number: 2,
+                end_block_number: 2,
+            }]
+        );
+        assert_eq!(blocks, [block_2()]);
+
+        // Codec v0 chunks are followed by the L2 transactions of their blocks, codec v1 chunks
+        // only have the block contexts.
+        chunk.extend([0, 0, 0, 4, 0xde, 0xad, 0xbe, 0xef]);
+        let (_, blocks) =
+            decode_block_ranges_from_encoded_chunks(CodecVersion::CodecV0, vec![chunk.clone()])
+                .unwrap();
+        assert_eq!(blocks, [block_2()]);
+        assert!(
+            decode_block_ranges_from_encoded_chunks(CodecVersion::CodecV1, vec![chunk]).is_err()
+        );
+    }
+
+    #[test]
+    fn decode_fields_at_their_offsets() {
+        let base_fee = U256::from_dec_str("123456789012345678901234567890").unwrap();
+        let mut context = [0; BLOCK_CONTEXT_LENGTH];
+        context[0..8].copy_from_slice(&1u64.to_be_bytes());
+        context[8..16].copy_from_slice(&2u64.to_be_bytes());
+        base_fee.to_big_endian(&mut context[16..48]);
+        context[48..56].copy_from_slice(&3u64.to_be_bytes());
+        context[56..58].copy_from_slice(&4u16.to_be_bytes());
+        context[58..60].copy_from_slice(&5u16.to_be_bytes());
+
+        let expected = BlockContext {
+            number: 1,
+            timestamp: 2,
+            base_fee,
+            gas_limit: 3,
+            num_transactions: 4,
+            num_l1_messages: 5,
+        };
+        for codec_version in [CodecVersion::CodecV0, CodecVersion::CodecV1] {
+            assert_eq!(
+                BlockContext::decode(&codec_version, &context).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn block_context_rlp_round_trip() {
+        let mut block = block_2();
+        block.base_fee = U256::MAX;
+        for block in [block_2(), block] {
+            let encoded = rlp::encode(&block);
+            assert_eq!(rlp::decode::<BlockContext>(&encoded).unwrap(), block);
+        }
+    }
 }
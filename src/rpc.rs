@@ -0,0 +1,229 @@
+use std::{
+    env,
+    net::SocketAddr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use ethers::types::H256;
+use jsonrpsee::{
+    core::RpcResult,
+    proc_macros::rpc,
+    server::ServerBuilder,
+    types::{error::INTERNAL_ERROR_CODE, error::INVALID_PARAMS_CODE, ErrorObjectOwned},
+};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::{
+    batch_status::BatchStatus,
+    db::{Batch, FinalizedBatchRoots},
+    rollup_sync_service_util::BlockContext,
+    status::SyncStatus,
+    storage::Storage,
+    sync_service::L1MessageTx,
+    withdraw_trie::{build_withdrawal_proof, WithdrawalProof},
+};
+
+const DEFAULT_RPC_ADDR: &str = "127.0.0.1:8545";
+/// Maximum number of messages returned by `scroll_getL1MessagesInRange`.
+const MAX_L1_MESSAGES_PER_REQUEST: u64 = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatusResponse {
+    pub l1_head: u64,
+    pub l1_message_synced_block: u64,
+    pub rollup_synced_block: u64,
+    pub last_l1_message_index: Option<u64>,
+    pub last_committed_batch_index: Option<u64>,
+    pub last_finalized_batch_index: Option<u64>,
+}
+
+#[rpc(server, namespace = "scroll")]
+pub trait ScrollApi {
+    #[method(name = "getL1MessageByIndex")]
+    fn l1_message_by_index(&self, queue_index: u64) -> RpcResult<Option<L1MessageTx>>;
+
+    /// Messages with a queue index in `[from, to]`, skipping the ones that are not synced.
+    #[method(name = "getL1MessagesInRange")]
+    fn l1_messages_in_range(&self, from: u64, to: u64) -> RpcResult<Vec<L1MessageTx>>;
+
+    #[method(name = "getBatchByIndex")]
+    fn batch_by_index(&self, batch_index: u64) -> RpcResult<Option<Batch>>;
+
+    #[method(name = "getBatchForBlock")]
+    fn batch_for_block(&self, l2_block_number: u64) -> RpcResult<Option<Batch>>;
+
+    /// Lifecycle of a batch, `null` for batches synced before it was tracked.
+    #[method(name = "getBatchStatus")]
+    fn batch_status(&self, batch_index: u64) -> RpcResult<Option<BatchStatus>>;
+
+    /// Batches committed more than `min_age_secs` ago that are not finalized yet.
+    #[method(name = "getUnfinalizedBatches")]
+    fn unfinalized_batches(&self, min_age_secs: u64) -> RpcResult<Vec<BatchStatus>>;
+
+    /// State root and withdraw root of the latest finalized batch, if it covers the block.
+    #[method(name = "getFinalizedRootsForBlock")]
+    fn finalized_roots_for_block(
+        &self,
+        l2_block_number: u64,
+    ) -> RpcResult<Option<FinalizedBatchRoots>>;
+
+    /// Proves the withdrawal with `nonce` against the latest finalized withdraw root, given
+    /// the withdraw trie leaves ordered by nonce. `null` until a withdraw root is finalized.
+    #[method(name = "getWithdrawalProof")]
+    fn withdrawal_proof(&self, nonce: u64, leaves: Vec<H256>)
+        -> RpcResult<Option<WithdrawalProof>>;
+
+    /// Context of an L2 block as committed on L1, `null` if its batch isn't synced or was
+    /// synced before block contexts were recorded.
+    #[method(name = "getBlockContext")]
+    fn block_context(&self, l2_block_number: u64) -> RpcResult<Option<BlockContext>>;
+
+    #[method(name = "syncStatus")]
+    fn sync_status(&self) -> RpcResult<SyncStatusResponse>;
+}
+
+fn internal_error(err: impl ToString) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>)
+}
+
+/// Serves the synced L1 messages and batches over JSON-RPC.
+#[derive(Debug, Clone)]
+pub struct ScrollRpc {
+    storage: Arc<dyn Storage>,
+    status: Arc<SyncStatus>,
+}
+
+impl ScrollRpc {
+    pub fn new(storage: Arc<dyn Storage>, status: Arc<SyncStatus>) -> Self {
+        Self { storage, status }
+    }
+}
+
+impl ScrollApiServer for ScrollRpc {
+    fn l1_message_by_index(&self, queue_index: u64) -> RpcResult<Option<L1MessageTx>> {
+        self.storage.l1_message(queue_index).map_err(internal_error)
+    }
+
+    fn l1_messages_in_range(&self, from: u64, to: u64) -> RpcResult<Vec<L1MessageTx>> {
+        if to < from || to - from >= MAX_L1_MESSAGES_PER_REQUEST {
+            return Err(ErrorObjectOwned::owned(
+                INVALID_PARAMS_CODE,
+                format!(
+                    "invalid range, at most {} messages can be requested",
+                    MAX_L1_MESSAGES_PER_REQUEST
+                ),
+                None::<()>,
+            ));
+        }
+        self.storage
+            .l1_messages_in_range(from, to)
+            .map_err(internal_error)
+    }
+
+    fn batch_by_index(&self, batch_index: u64) -> RpcResult<Option<Batch>> {
+        self.storage.batch(batch_index).map_err(internal_error)
+    }
+
+    fn batch_for_block(&self, l2_block_number: u64) -> RpcResult<Option<Batch>> {
+        self.storage
+            .batch_for_block(l2_block_number)
+            .map_err(internal_error)
+    }
+
+    fn batch_status(&self, batch_index: u64) -> RpcResult<Option<BatchStatus>> {
+        self.storage
+            .batch_status(batch_index)
+            .map_err(internal_error)
+    }
+
+    fn unfinalized_batches(&self, min_age_secs: u64) -> RpcResult<Vec<BatchStatus>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(internal_error)?
+            .as_secs();
+        self.storage
+            .unfinalized_batches(now.saturating_sub(min_age_secs))
+            .map_err(internal_error)
+    }
+
+    fn finalized_roots_for_block(
+        &self,
+        l2_block_number: u64,
+    ) -> RpcResult<Option<FinalizedBatchRoots>> {
+        self.storage
+            .finalized_roots_for_block(l2_block_number)
+            .map_err(internal_error)
+    }
+
+    fn withdrawal_proof(
+        &self,
+        nonce: u64,
+        leaves: Vec<H256>,
+    ) -> RpcResult<Option<WithdrawalProof>> {
+        let Some(finalized) = self
+            .storage
+            .latest_finalized_roots()
+            .map_err(internal_error)?
+        else {
+            return Ok(None);
+        };
+        build_withdrawal_proof(&leaves, nonce, finalized)
+            .map(Some)
+            .map_err(|err| {
+                ErrorObjectOwned::owned(INVALID_PARAMS_CODE, err.to_string(), None::<()>)
+            })
+    }
+
+    fn block_context(&self, l2_block_number: u64) -> RpcResult<Option<BlockContext>> {
+        self.storage
+            .block_context(l2_block_number)
+            .map_err(internal_error)
+    }
+
+    fn sync_status(&self) -> RpcResult<SyncStatusResponse> {
+        let cursors = self.storage.cursors().map_err(internal_error)?;
+        Ok(SyncStatusResponse {
+            l1_head: self.status.l1_head(),
+            l1_message_synced_block: self.status.l1_message_synced_block(),
+            rollup_synced_block: self.status.rollup_synced_block(),
+            last_l1_message_index: cursors.last_l1_message_index,
+            last_committed_batch_index: cursors.last_committed_batch_index,
+            last_finalized_batch_index: cursors.last_finalized_batch_index,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RpcServerConfig {
+    pub addr: SocketAddr,
+}
+
+impl RpcServerConfig {
+    pub fn from_env() -> Self {
+        let addr = env::var("RPC_ADDR").unwrap_or_else(|_| DEFAULT_RPC_ADDR.to_string());
+        Self {
+            addr: addr.parse().expect("Invalid RPC_ADDR"),
+        }
+    }
+}
+
+/// Runs the JSON-RPC server until `shutdown` is cancelled.
+pub async fn run_rpc_server(
+    config: RpcServerConfig,
+    rpc: ScrollRpc,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let server = ServerBuilder::default().build(config.addr).await?;
+    info!("JSON-RPC server listening on {}", server.local_addr()?);
+    let handle = server.start(rpc.into_rpc());
+
+    shutdown.cancelled().await;
+    handle.stop()?;
+    handle.stopped().await;
+    Ok(())
+}
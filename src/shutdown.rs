@@ -0,0 +1,22 @@
+use tokio::signal::ctrl_c;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::info;
+
+/// Resolves once the process receives Ctrl-C or, on unix, SIGTERM.
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        let mut sigterm =
+            signal(SignalKind::terminate()).expect("Could not install SIGTERM handler");
+        tokio::select! {
+            _ = ctrl_c() => info!("Received Ctrl-C"),
+            _ = sigterm.recv() => info!("Received SIGTERM"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        ctrl_c().await.expect("Could not listen for Ctrl-C");
+        info!("Received Ctrl-C");
+    }
+}
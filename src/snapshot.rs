@@ -0,0 +1,647 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use reth_db::{
+    cursor::DbCursorRO,
+    database::Database,
+    transaction::{DbTx, DbTxMut},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{
+    batch_status::{BatchStatus, FinalizedRoots},
+    db::{
+        get_batch, get_batch_l1_block, get_batch_status, get_batch_total_l1_messages_popped,
+        get_block_contexts_in_range, get_committed_batch_bounds, get_cursors, get_finalized_roots,
+        get_l1_message_block, init_db, open_db_read_only, Cursors, FIRST_COMMITTED_BATCH_INDEX_KEY,
+        LAST_COMMITTED_BATCH_INDEX_KEY, LAST_FINALIZED_BATCH_INDEX_KEY, LAST_L1_MESSAGE_INDEX_KEY,
+        LAST_SYNCED_L1_BLOCK_KEY, ROLLUP_LAST_SYNCED_L1_BLOCK_KEY,
+    },
+    rollup_sync_service_util::{BlockContext, ChunkBlockRange},
+    sync_service::L1MessageTx,
+    tables::{
+        BatchBlockRanges, BatchBlocks, BatchChunkRanges, BatchFinalizedRoots,
+        BatchL1MessagesPopped, BatchStatuses, BlockContexts, ChunkRanges, L1MessageBlocks,
+        L1Messages, Metadata,
+    },
+};
+
+const SNAPSHOT_VERSION: u64 = 1;
+/// Number of records written per database transaction during an import.
+const IMPORT_COMMIT_INTERVAL: u64 = 50_000;
+
+/// A line of a snapshot file. A snapshot is a header, the L1 messages and batches, and a
+/// trailer with the record counts and the SHA-256 of every line before it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum SnapshotRecord {
+    Header {
+        version: u64,
+        cursors: Cursors,
+    },
+    /// The L1 block is absent for records synced before it was recorded.
+    L1Message {
+        #[serde(
+            rename = "l1BlockNumber",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        l1_block_number: Option<u64>,
+        #[serde(flatten)]
+        message: L1MessageTx,
+    },
+    /// The chunks are empty for pruned batches, and so is the block range for batches
+    /// committed without chunks. The status and block contexts are absent for batches synced
+    /// before they were recorded.
+    Batch {
+        #[serde(rename = "batchIndex")]
+        batch_index: u64,
+        #[serde(
+            rename = "firstBlock",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        first_block: Option<u64>,
+        #[serde(rename = "lastBlock", default, skip_serializing_if = "Option::is_none")]
+        last_block: Option<u64>,
+        #[serde(default)]
+        chunks: Vec<ChunkBlockRange>,
+        #[serde(
+            rename = "l1BlockNumber",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        l1_block_number: Option<u64>,
+        #[serde(
+            rename = "totalL1MessagesPopped",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        total_l1_messages_popped: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        status: Option<BatchStatus>,
+        #[serde(
+            rename = "finalizedRoots",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        finalized_roots: Option<FinalizedRoots>,
+        #[serde(
+            rename = "blockContexts",
+            default,
+            skip_serializing_if = "Vec::is_empty"
+        )]
+        block_contexts: Vec<BlockContext>,
+    },
+    Trailer {
+        #[serde(rename = "l1Messages")]
+        l1_messages: u64,
+        batches: u64,
+        sha256: String,
+    },
+}
+
+/// Writes every stored L1 message and batch along with the sync cursors to `output`.
+pub fn run_snapshot(datadir: &Path, output: &Path) -> Result<()> {
+    if output.exists() {
+        bail!("{} already exists", output.display());
+    }
+    let db = open_db_read_only(datadir)?;
+    // A single read transaction, so that the cursors match the data.
+    let tx = db.tx()?;
+    let mut writer = SnapshotWriter::new(File::create(output)?);
+
+    writer.write(&SnapshotRecord::Header {
+        version: SNAPSHOT_VERSION,
+        cursors: get_cursors(&tx)?,
+    })?;
+
+    let mut l1_messages = 0;
+    let mut cursor = tx.cursor_read::<L1Messages>()?;
+    for entry in cursor.walk(None)? {
+        let (queue_index, message) = entry?;
+        writer.write(&SnapshotRecord::L1Message {
+            l1_block_number: get_l1_message_block(&tx, queue_index)?,
+            message,
+        })?;
+        l1_messages += 1;
+    }
+
+    // Batches committed without chunks have no block range, so walk the committed indices
+    // rather than the block ranges.
+    let mut batches = 0;
+    let batch_indices =
+        get_committed_batch_bounds(&tx)?.map_or(1..=0, |(first, last)| first..=last);
+    for batch_index in batch_indices {
+        let batch = get_batch(&tx, batch_index)?;
+        let l1_block_number = get_batch_l1_block(&tx, batch_index)?;
+        let status = get_batch_status(&tx, batch_index)?;
+        if batch.is_none() && l1_block_number.is_none() && status.is_none() {
+            continue;
+        }
+        let block_contexts = match &batch {
+            Some(batch) => get_block_contexts_in_range(
+                &tx,
+                &ChunkBlockRange {
+                    start_block_number: batch.first_block,
+                    end_block_number: batch.last_block,
+                },
+            )?,
+            None => vec![],
+        };
+        writer.write(&SnapshotRecord::Batch {
+            batch_index,
+            first_block: batch.as_ref().map(|batch| batch.first_block),
+            last_block: batch.as_ref().map(|batch| batch.last_block),
+            chunks: batch.map(|batch| batch.chunks).unwrap_or_default(),
+            l1_block_number,
+            total_l1_messages_popped: get_batch_total_l1_messages_popped(&tx, batch_index)?,
+            status,
+            finalized_roots: get_finalized_roots(&tx, batch_index)?,
+            block_contexts,
+        })?;
+        batches += 1;
+    }
+
+    writer.finish(l1_messages, batches)?;
+    info!(
+        "Wrote snapshot of {} L1 messages and {} batches to {}",
+        l1_messages,
+        batches,
+        output.display()
+    );
+    Ok(())
+}
+
+/// Verifies the snapshot at `input` and loads it into an empty database. The cursors are
+/// written last, so an interrupted import leaves a database the node starts syncing from
+/// genesis on, and the import can simply be run again.
+pub fn run_import(datadir: &Path, input: &Path) -> Result<()> {
+    let cursors = verify_snapshot(input)?;
+    info!("Snapshot {} verified", input.display());
+
+    let db = init_db(datadir)?;
+    let existing = get_cursors(&db.tx()?)?;
+    if existing.last_synced_l1_block.is_some() || existing.rollup_last_synced_l1_block.is_some() {
+        bail!(
+            "{} already contains synced data, import into a new data directory",
+            datadir.display()
+        );
+    }
+
+    let mut tx = db.tx_mut()?;
+    let mut imported = 0;
+    for line in BufReader::new(File::open(input)?).lines() {
+        match serde_json::from_str(&line?)? {
+            SnapshotRecord::L1Message {
+                l1_block_number,
+                message,
+            } => {
+                let queue_index = message.queue_index;
+                tx.put::<L1Messages>(queue_index, message)?;
+                if let Some(l1_block_number) = l1_block_number {
+                    tx.put::<L1MessageBlocks>(queue_index, l1_block_number)?;
+                }
+            }
+            SnapshotRecord::Batch {
+                batch_index,
+                first_block,
+                last_block,
+                chunks,
+                l1_block_number,
+                total_l1_messages_popped,
+                status,
+                finalized_roots,
+                block_contexts,
+            } => {
+                if let Some((start_block_number, end_block_number)) = first_block.zip(last_block) {
+                    let range = ChunkBlockRange {
+                        start_block_number,
+                        end_block_number,
+                    };
+                    tx.put::<BatchBlockRanges>(batch_index, range)?;
+                }
+                if !chunks.is_empty() {
+                    tx.put::<BatchChunkRanges>(batch_index, ChunkRanges(chunks))?;
+                }
+                if let Some(total_l1_messages_popped) = total_l1_messages_popped {
+                    tx.put::<BatchL1MessagesPopped>(batch_index, total_l1_messages_popped)?;
+                }
+                if let Some(l1_block_number) = l1_block_number {
+                    tx.put::<BatchBlocks>(batch_index, l1_block_number)?;
+                }
+                if let Some(status) = status {
+                    tx.put::<BatchStatuses>(batch_index, status)?;
+                }
+                if let Some(finalized_roots) = finalized_roots {
+                    tx.put::<BatchFinalizedRoots>(batch_index, finalized_roots)?;
+                }
+                for block_context in block_contexts {
+                    tx.put::<BlockContexts>(block_context.number, block_context)?;
+                }
+            }
+            SnapshotRecord::Header { .. } | SnapshotRecord::Trailer { .. } => continue,
+        }
+        imported += 1;
+        if imported % IMPORT_COMMIT_INTERVAL == 0 {
+            tx.commit()?;
+            tx = db.tx_mut()?;
+            info!("Imported {} records", imported);
+        }
+    }
+
+    let l1_keys = [
+        (LAST_SYNCED_L1_BLOCK_KEY, cursors.last_synced_l1_block),
+        (LAST_L1_MESSAGE_INDEX_KEY, cursors.last_l1_message_index),
+    ];
+    for (key, value) in l1_keys {
+        if let Some(value) = value {
+            tx.put::<Metadata>(key.to_string(), value)?;
+        }
+    }
+    let rollup_keys = [
+        (
+            ROLLUP_LAST_SYNCED_L1_BLOCK_KEY,
+            cursors.rollup_last_synced_l1_block,
+        ),
+        (
+            FIRST_COMMITTED_BATCH_INDEX_KEY,
+            cursors.first_committed_batch_index,
+        ),
+        (
+            LAST_COMMITTED_BATCH_INDEX_KEY,
+            cursors.last_committed_batch_index,
+        ),
+        (
+            LAST_FINALIZED_BATCH_INDEX_KEY,
+            cursors.last_finalized_batch_index,
+        ),
+    ];
+    for (key, value) in rollup_keys {
+        if let Some(value) = value {
+            tx.put::<Metadata>(key.to_string(), value)?;
+        }
+    }
+    tx.commit()?;
+
+    info!(
+        "Imported {} records, L1 messages synced to block {:?}, rollup events synced to block {:?}",
+        imported, cursors.last_synced_l1_block, cursors.rollup_last_synced_l1_block
+    );
+    Ok(())
+}
+
+/// Checks the checksum, the record counts and that the records are consistent with the
+/// cursors of the header, returning the cursors.
+fn verify_snapshot(input: &Path) -> Result<Cursors> {
+    let mut reader = BufReader::new(
+        File::open(input).with_context(|| format!("Could not open {}", input.display()))?,
+    );
+    let mut hasher = Sha256::new();
+    let mut cursors = None;
+    let mut trailer = None;
+    let mut queue_indices = HashSet::new();
+    let mut batch_indices = HashSet::new();
+
+    let mut line = String::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        ensure!(
+            trailer.is_none(),
+            "Line {}: unexpected data after the trailer",
+            line_number
+        );
+        let record: SnapshotRecord = serde_json::from_str(&line)
+            .with_context(|| format!("Line {}: invalid record", line_number))?;
+
+        match record {
+            SnapshotRecord::Header {
+                version,
+                cursors: header_cursors,
+            } => {
+                ensure!(line_number == 1, "Line {}: unexpected header", line_number);
+                ensure!(
+                    version == SNAPSHOT_VERSION,
+                    "Unsupported snapshot version {}, expected {}",
+                    version,
+                    SNAPSHOT_VERSION
+                );
+                cursors = Some(header_cursors);
+            }
+            SnapshotRecord::L1Message { message, .. } => {
+                ensure!(
+                    queue_indices.insert(message.queue_index),
+                    "Line {}: duplicate L1 message {}",
+                    line_number,
+                    message.queue_index
+                );
+            }
+            SnapshotRecord::Batch {
+                batch_index,
+                first_block,
+                last_block,
+                chunks,
+                ..
+            } => {
+                verify_batch(batch_index, first_block.zip(last_block), &chunks)
+                    .with_context(|| format!("Line {}", line_number))?;
+                ensure!(
+                    batch_indices.insert(batch_index),
+                    "Line {}: duplicate batch {}",
+                    line_number,
+                    batch_index
+                );
+            }
+            SnapshotRecord::Trailer {
+                l1_messages,
+                batches,
+                sha256,
+            } => {
+                trailer = Some((l1_messages, batches, sha256));
+                // The trailer itself is not part of the checksum.
+                continue;
+            }
+        }
+        hasher.update(line.as_bytes());
+    }
+
+    let Some(cursors) = cursors else {
+        bail!("Snapshot has no header");
+    };
+    let Some((l1_messages, batches, sha256)) = trailer else {
+        bail!("Snapshot has no trailer, it is probably truncated");
+    };
+    let actual_sha256 = alloy_primitives::hex::encode(hasher.finalize());
+    ensure!(
+        actual_sha256 == sha256,
+        "Checksum mismatch: trailer says {}, content hashes to {}",
+        sha256,
+        actual_sha256
+    );
+    ensure!(
+        queue_indices.len() as u64 == l1_messages && batch_indices.len() as u64 == batches,
+        "Record counts don't match the trailer"
+    );
+
+    // L1 messages are stored without gaps, up to the last message index.
+    if let (Some(first), Some(last)) = (queue_indices.iter().min(), queue_indices.iter().max()) {
+        ensure!(
+            cursors.last_l1_message_index == Some(*last),
+            "Last L1 message {} doesn't match the cursor {:?}",
+            last,
+            cursors.last_l1_message_index
+        );
+        ensure!(
+            last - first + 1 == l1_messages,
+            "L1 messages {} to {} have gaps",
+            first,
+            last
+        );
+    }
+    // Batches may have gaps, but must lie within the committed bounds.
+    if let Some((first, last)) = cursors
+        .first_committed_batch_index
+        .zip(cursors.last_committed_batch_index)
+    {
+        ensure!(
+            batch_indices
+                .iter()
+                .all(|batch_index| (first..=last).contains(batch_index)),
+            "Batches outside of the committed bounds {} to {}",
+            first,
+            last
+        );
+    } else {
+        ensure!(batch_indices.is_empty(), "Batches without committed bounds");
+    }
+    Ok(cursors)
+}
+
+/// Checks the block range of a batch against its chunks. `range` is the first and last block,
+/// `None` for a batch committed without chunks.
+fn verify_batch(
+    batch_index: u64,
+    range: Option<(u64, u64)>,
+    chunks: &[ChunkBlockRange],
+) -> Result<()> {
+    let Some((first_block, last_block)) = range else {
+        ensure!(
+            chunks.is_empty(),
+            "batch {} has chunks but no block range",
+            batch_index
+        );
+        return Ok(());
+    };
+    let (Some(first_chunk), Some(last_chunk)) = (chunks.first(), chunks.last()) else {
+        // A pruned batch, only its block range is left.
+        ensure!(
+            first_block <= last_block,
+            "batch {} has an invalid block range",
+            batch_index
+        );
+        return Ok(());
+    };
+    ensure!(
+        first_block == first_chunk.start_block_number && last_block == last_chunk.end_block_number,
+        "batch {} block range doesn't match its chunks",
+        batch_index
+    );
+    ensure!(
+        chunks
+            .iter()
+            .all(|chunk| chunk.start_block_number <= chunk.end_block_number),
+        "batch {} has an invalid chunk range",
+        batch_index
+    );
+    Ok(())
+}
+
+/// Writes snapshot records as JSON lines while hashing them for the trailer.
+struct SnapshotWriter {
+    writer: BufWriter<File>,
+    hasher: Sha256,
+}
+
+impl SnapshotWriter {
+    fn new(file: File) -> Self {
+        Self {
+            writer: BufWriter::new(file),
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn write(&mut self, record: &SnapshotRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.hasher.update(&line);
+        self.writer.write_all(&line)?;
+        Ok(())
+    }
+
+    fn finish(mut self, l1_messages: u64, batches: u64) -> Result<()> {
+        let trailer = SnapshotRecord::Trailer {
+            l1_messages,
+            batches,
+            sha256: alloy_primitives::hex::encode(self.hasher.finalize_reset()),
+        };
+        self.write(&trailer)?;
+        let file = self.writer.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use ethers::types::H256;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        storage::{BatchEvent, MdbxStorage, Storage},
+        test_utils::{chunk, committed, message, provenance},
+    };
+
+    /// Messages 0 to 3, batch 0 finalized, batch 1 committed without chunks and batch 2
+    /// committed, written to a snapshot next to the database.
+    fn synced_snapshot(datadir: &TempDir) -> PathBuf {
+        let storage = MdbxStorage::new(init_db(&datadir.path().join("db")).unwrap());
+        storage
+            .commit_l1_messages(
+                (0..4)
+                    .map(|queue_index| (message(queue_index), 100 + queue_index))
+                    .collect(),
+                110,
+            )
+            .unwrap();
+        storage
+            .commit_batch_events(
+                vec![
+                    committed(0, vec![chunk(0, 2), chunk(3, 4)], 0, 2, 101),
+                    committed(1, vec![], 2, 2, 102),
+                    committed(2, vec![chunk(5, 6)], 2, 4, 104),
+                    BatchEvent::Finalized {
+                        batch_index: 0,
+                        roots: FinalizedRoots {
+                            state_root: H256::repeat_byte(1),
+                            withdraw_root: H256::repeat_byte(2),
+                        },
+                        provenance: provenance(105),
+                    },
+                ],
+                110,
+            )
+            .unwrap();
+        drop(storage);
+
+        let output = datadir.path().join("snapshot.jsonl");
+        run_snapshot(&datadir.path().join("db"), &output).unwrap();
+        output
+    }
+
+    fn contents(storage: &dyn Storage) -> Value {
+        let batches: Vec<_> = (0..3)
+            .map(|batch_index| {
+                json!({
+                    "batch": storage.batch(batch_index).unwrap(),
+                    "status": storage.batch_status(batch_index).unwrap(),
+                    "popped": storage.batch_total_l1_messages_popped(batch_index).unwrap(),
+                })
+            })
+            .collect();
+        let blocks: Vec<_> = (0..7)
+            .map(|l2_block_number| storage.block_context(l2_block_number).unwrap())
+            .collect();
+        json!({
+            "cursors": storage.cursors().unwrap(),
+            "messages": storage.l1_messages_in_range(0, 3).unwrap(),
+            "batches": batches,
+            "blocks": blocks,
+            "roots": storage.latest_finalized_roots().unwrap(),
+        })
+    }
+
+    #[test]
+    fn snapshot_includes_batches_without_chunks() {
+        let datadir = TempDir::new().unwrap();
+        let snapshot = synced_snapshot(&datadir);
+
+        let cursors = verify_snapshot(&snapshot).unwrap();
+        assert_eq!(cursors.last_l1_message_index, Some(3));
+        assert_eq!(cursors.last_committed_batch_index, Some(2));
+        let contents = fs::read_to_string(&snapshot).unwrap();
+        let trailer: Value = serde_json::from_str(contents.lines().last().unwrap()).unwrap();
+        assert_eq!(trailer["l1Messages"], 4);
+        assert_eq!(trailer["batches"], 3);
+    }
+
+    #[test]
+    fn verify_snapshot_detects_a_flipped_byte() {
+        let datadir = TempDir::new().unwrap();
+        let snapshot = synced_snapshot(&datadir);
+
+        // Turns the data `0xcafe` of the first message into `0xcafd`, which still parses.
+        let mut contents = fs::read(&snapshot).unwrap();
+        let position = contents
+            .windows(4)
+            .position(|window| window == b"cafe")
+            .unwrap();
+        contents[position + 3] ^= 1;
+        fs::write(&snapshot, contents).unwrap();
+
+        let err = verify_snapshot(&snapshot).unwrap_err();
+        assert!(err.to_string().starts_with("Checksum mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn verify_snapshot_detects_a_truncated_trailer() {
+        let datadir = TempDir::new().unwrap();
+        let snapshot = synced_snapshot(&datadir);
+        let contents = fs::read(&snapshot).unwrap();
+
+        fs::write(&snapshot, &contents[..contents.len() - 10]).unwrap();
+        let err = verify_snapshot(&snapshot).unwrap_err();
+        assert!(err.to_string().contains("invalid record"), "{}", err);
+
+        // Cut right before the trailer.
+        let trailer_start = contents[..contents.len() - 1]
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .unwrap()
+            + 1;
+        fs::write(&snapshot, &contents[..trailer_start]).unwrap();
+        let err = verify_snapshot(&snapshot).unwrap_err();
+        assert!(err.to_string().contains("no trailer"), "{}", err);
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let datadir = TempDir::new().unwrap();
+        let snapshot = synced_snapshot(&datadir);
+        let imported_dir = datadir.path().join("imported");
+
+        run_import(&imported_dir, &snapshot).unwrap();
+
+        let original = MdbxStorage::new(init_db(&datadir.path().join("db")).unwrap());
+        let imported = MdbxStorage::new(init_db(&imported_dir).unwrap());
+        let imported_contents = contents(&imported);
+        assert_eq!(imported_contents, contents(&original));
+        assert!(imported_contents["batches"][1]["status"].is_object());
+        assert!(imported_contents["batches"][1]["batch"].is_null());
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::info;
+
+use crate::db::Cursors;
+
+/// Sync progress shared between the services, updated as they commit.
+#[derive(Debug, Default)]
+pub struct SyncStatus {
+    l1_head: AtomicU64,
+    l1_confirmed_head: AtomicU64,
+    l1_message_synced_block: AtomicU64,
+    rollup_synced_block: AtomicU64,
+    l1_messages_indexed: AtomicU64,
+    batches_indexed: AtomicU64,
+}
+
+impl SyncStatus {
+    pub fn l1_head(&self) -> u64 {
+        self.l1_head.load(Ordering::Relaxed)
+    }
+
+    pub fn set_l1_head(&self, block_number: u64) {
+        self.l1_head.store(block_number, Ordering::Relaxed);
+    }
+
+    /// L1 head minus the confirmation depth, the block the services sync up to.
+    pub fn l1_confirmed_head(&self) -> u64 {
+        self.l1_confirmed_head.load(Ordering::Relaxed)
+    }
+
+    pub fn set_l1_confirmed_head(&self, block_number: u64) {
+        self.l1_confirmed_head
+            .store(block_number, Ordering::Relaxed);
+    }
+
+    pub fn l1_message_synced_block(&self) -> u64 {
+        self.l1_message_synced_block.load(Ordering::Relaxed)
+    }
+
+    pub fn set_l1_message_synced_block(&self, block_number: u64) {
+        self.l1_message_synced_block
+            .store(block_number, Ordering::Relaxed);
+    }
+
+    pub fn rollup_synced_block(&self) -> u64 {
+        self.rollup_synced_block.load(Ordering::Relaxed)
+    }
+
+    pub fn set_rollup_synced_block(&self, block_number: u64) {
+        self.rollup_synced_block
+            .store(block_number, Ordering::Relaxed);
+    }
+
+    /// L1 messages stored since the node started.
+    pub fn l1_messages_indexed(&self) -> u64 {
+        self.l1_messages_indexed.load(Ordering::Relaxed)
+    }
+
+    pub fn add_l1_messages_indexed(&self, count: u64) {
+        self.l1_messages_indexed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Committed batches stored since the node started.
+    pub fn batches_indexed(&self) -> u64 {
+        self.batches_indexed.load(Ordering::Relaxed)
+    }
+
+    pub fn add_batches_indexed(&self, count: u64) {
+        self.batches_indexed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Sets the synced blocks from the cursors stored in the database, for processes reading a
+    /// database that is synced by another one.
+    pub fn set_from_cursors(&self, cursors: &Cursors) {
+        self.set_l1_message_synced_block(cursors.last_synced_l1_block.unwrap_or_default());
+        self.set_rollup_synced_block(cursors.rollup_last_synced_l1_block.unwrap_or_default());
+    }
+
+    pub fn report(&self) {
+        info!(
+            "L1 head: {}, L1 messages synced to: {}, rollup events synced to: {}",
+            self.l1_head(),
+            self.l1_message_synced_block(),
+            self.rollup_synced_block()
+        );
+    }
+}
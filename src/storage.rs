@@ -0,0 +1,945 @@
+use std::{collections::BTreeMap, fmt::Debug, sync::RwLock};
+
+use anyhow::Result;
+use reth_db::{
+    cursor::{DbCursorRO, DbCursorRW},
+    database::Database,
+    transaction::{DbTx, DbTxMut},
+    DatabaseEnv,
+};
+
+use crate::{
+    batch_status::{BatchState, BatchStatus, FinalizedRoots, L1Provenance},
+    db::{
+        count_l1_messages_in_range, find_batch_for_block, get_batch, get_batch_block_range,
+        get_batch_status, get_batch_total_l1_messages_popped, get_block_context,
+        get_committed_batch_bounds, get_cursors, get_first_l1_message_index, get_l1_message,
+        get_last_finalized_batch_index, get_latest_finalized_roots, get_unfinalized_batches, Batch,
+        Cursors, FinalizedBatchRoots, FIRST_COMMITTED_BATCH_INDEX_KEY,
+        LAST_COMMITTED_BATCH_INDEX_KEY, LAST_FINALIZED_BATCH_INDEX_KEY, LAST_L1_MESSAGE_INDEX_KEY,
+        LAST_SYNCED_L1_BLOCK_KEY, ROLLUP_LAST_SYNCED_L1_BLOCK_KEY,
+    },
+    pruner::{PruneConfig, PruneStats},
+    rollup_sync_service_util::{BlockContext, ChunkBlockRange},
+    sync_service::L1MessageTx,
+    tables::{
+        BatchBlockRanges, BatchBlocks, BatchChunkRanges, BatchFinalizedRoots,
+        BatchL1MessagesPopped, BatchStatuses, BlockContexts, ChunkRanges, L1MessageBlocks,
+        L1Messages, Metadata,
+    },
+};
+
+/// Most entries of each kind removed by a single prune, to bound the size of its transaction.
+/// Whatever is left is removed by the next runs.
+const MAX_PRUNED_PER_RUN: u64 = 100_000;
+
+/// A batch decoded from a `CommitBatch` event and its transaction.
+#[derive(Debug, Clone)]
+pub struct CommittedBatch {
+    pub batch_index: u64,
+    pub chunks: Vec<ChunkBlockRange>,
+    /// Every block of the batch, in order.
+    pub blocks: Vec<BlockContext>,
+    /// L1 messages popped before this batch, as recorded in its parent batch header.
+    pub parent_total_l1_messages_popped: u64,
+    /// L1 messages popped up to and including this batch.
+    pub total_l1_messages_popped: u64,
+    pub provenance: L1Provenance,
+}
+
+/// A rollup event, in the order it was emitted on L1.
+#[derive(Debug, Clone)]
+pub enum BatchEvent {
+    Committed(CommittedBatch),
+    /// Finalizes every committed batch after the last finalized one, up to and including
+    /// `batch_index`.
+    Finalized {
+        batch_index: u64,
+        roots: FinalizedRoots,
+        provenance: L1Provenance,
+    },
+    Reverted {
+        batch_index: u64,
+        provenance: L1Provenance,
+    },
+}
+
+/// The follower's data as seen by the sync services and the RPC server. Every commit stores
+/// its data together with the cursor it moves, so readers never see a cursor ahead of the data.
+pub trait Storage: Debug + Send + Sync {
+    fn cursors(&self) -> Result<Cursors>;
+
+    fn l1_message(&self, queue_index: u64) -> Result<Option<L1MessageTx>>;
+
+    /// Lowest queue index stored, messages below it were queued before the genesis block or
+    /// pruned.
+    fn first_l1_message_index(&self) -> Result<Option<u64>>;
+
+    /// Messages with a queue index in `[from, to]`, skipping the ones that are not stored.
+    fn l1_messages_in_range(&self, from: u64, to: u64) -> Result<Vec<L1MessageTx>>;
+
+    /// Number of messages stored with a queue index in `[from, to]`.
+    fn l1_messages_count_in_range(&self, from: u64, to: u64) -> Result<u64>;
+
+    fn batch(&self, batch_index: u64) -> Result<Option<Batch>>;
+
+    /// Committed batch whose chunks contain `l2_block_number`.
+    fn batch_for_block(&self, l2_block_number: u64) -> Result<Option<Batch>>;
+
+    /// Context of an L2 block as committed on L1.
+    fn block_context(&self, l2_block_number: u64) -> Result<Option<BlockContext>>;
+
+    /// L1 messages popped up to and including the batch.
+    fn batch_total_l1_messages_popped(&self, batch_index: u64) -> Result<Option<u64>>;
+
+    /// Stores messages along with the L1 block each one was emitted in and moves the L1
+    /// message cursor to `last_block`.
+    fn commit_l1_messages(&self, messages: Vec<(L1MessageTx, u64)>, last_block: u64) -> Result<()>;
+
+    fn batch_status(&self, batch_index: u64) -> Result<Option<BatchStatus>>;
+
+    /// Batches that are committed but not finalized, and were committed in an L1 block older
+    /// than `committed_before` (a Unix timestamp).
+    fn unfinalized_batches(&self, committed_before: u64) -> Result<Vec<BatchStatus>>;
+
+    /// Roots of the latest finalized batch that has them.
+    fn latest_finalized_roots(&self) -> Result<Option<FinalizedBatchRoots>>;
+
+    /// Roots of the latest finalized batch, if they cover `l2_block_number`. The withdraw trie
+    /// only grows and the state root includes every earlier block, so the latest roots are the
+    /// ones to prove against.
+    fn finalized_roots_for_block(
+        &self,
+        l2_block_number: u64,
+    ) -> Result<Option<FinalizedBatchRoots>> {
+        Ok(self
+            .latest_finalized_roots()?
+            .filter(|finalized| l2_block_number <= finalized.last_block))
+    }
+
+    /// Applies the rollup events in order and moves the rollup cursor to `last_block`.
+    fn commit_batch_events(&self, events: Vec<BatchEvent>, last_block: u64) -> Result<()>;
+
+    /// Removes the data of finalized batches that `config` doesn't keep.
+    fn prune(&self, config: &PruneConfig) -> Result<PruneStats>;
+}
+
+/// Storage in the MDBX database of the data directory.
+#[derive(Debug)]
+pub struct MdbxStorage {
+    db: DatabaseEnv,
+}
+
+impl MdbxStorage {
+    pub fn new(db: DatabaseEnv) -> Self {
+        Self { db }
+    }
+}
+
+impl Storage for MdbxStorage {
+    fn cursors(&self) -> Result<Cursors> {
+        get_cursors(&self.db.tx()?)
+    }
+
+    fn l1_message(&self, queue_index: u64) -> Result<Option<L1MessageTx>> {
+        get_l1_message(&self.db.tx()?, queue_index)
+    }
+
+    fn first_l1_message_index(&self) -> Result<Option<u64>> {
+        get_first_l1_message_index(&self.db.tx()?)
+    }
+
+    fn l1_messages_in_range(&self, from: u64, to: u64) -> Result<Vec<L1MessageTx>> {
+        let tx = self.db.tx()?;
+        let mut messages = vec![];
+        for queue_index in from..=to {
+            if let Some(message) = get_l1_message(&tx, queue_index)? {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+
+    fn l1_messages_count_in_range(&self, from: u64, to: u64) -> Result<u64> {
+        count_l1_messages_in_range(&self.db.tx()?, from, to)
+    }
+
+    fn batch(&self, batch_index: u64) -> Result<Option<Batch>> {
+        get_batch(&self.db.tx()?, batch_index)
+    }
+
+    fn batch_for_block(&self, l2_block_number: u64) -> Result<Option<Batch>> {
+        let tx = self.db.tx()?;
+        match find_batch_for_block(&tx, l2_block_number)? {
+            Some(batch_index) => get_batch(&tx, batch_index),
+            None => Ok(None),
+        }
+    }
+
+    fn block_context(&self, l2_block_number: u64) -> Result<Option<BlockContext>> {
+        get_block_context(&self.db.tx()?, l2_block_number)
+    }
+
+    fn batch_total_l1_messages_popped(&self, batch_index: u64) -> Result<Option<u64>> {
+        get_batch_total_l1_messages_popped(&self.db.tx()?, batch_index)
+    }
+
+    fn commit_l1_messages(&self, messages: Vec<(L1MessageTx, u64)>, last_block: u64) -> Result<()> {
+        let tx = self.db.tx_mut()?;
+        tx.put::<Metadata>(LAST_SYNCED_L1_BLOCK_KEY.to_string(), last_block)?;
+        let mut last_message_index = None;
+        for (message, l1_block_number) in messages {
+            let queue_index = message.queue_index;
+            tx.put::<L1Messages>(queue_index, message)?;
+            tx.put::<L1MessageBlocks>(queue_index, l1_block_number)?;
+            last_message_index = Some(queue_index);
+        }
+        if let Some(last_message_index) = last_message_index {
+            tx.put::<Metadata>(LAST_L1_MESSAGE_INDEX_KEY.to_string(), last_message_index)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn batch_status(&self, batch_index: u64) -> Result<Option<BatchStatus>> {
+        get_batch_status(&self.db.tx()?, batch_index)
+    }
+
+    fn unfinalized_batches(&self, committed_before: u64) -> Result<Vec<BatchStatus>> {
+        get_unfinalized_batches(&self.db.tx()?, committed_before)
+    }
+
+    fn latest_finalized_roots(&self) -> Result<Option<FinalizedBatchRoots>> {
+        get_latest_finalized_roots(&self.db.tx()?)
+    }
+
+    fn commit_batch_events(&self, events: Vec<BatchEvent>, last_block: u64) -> Result<()> {
+        let tx = self.db.tx_mut()?;
+        tx.put::<Metadata>(ROLLUP_LAST_SYNCED_L1_BLOCK_KEY.to_string(), last_block)?;
+        for event in events {
+            match event {
+                BatchEvent::Committed(batch) => {
+                    let batch_index = batch.batch_index;
+                    // A batch committed again after a revert may cover fewer blocks than before.
+                    if let Some(previous) = get_batch_block_range(&tx, batch_index)? {
+                        for l2_block_number in
+                            previous.start_block_number..=previous.end_block_number
+                        {
+                            tx.delete::<BlockContexts>(l2_block_number, None)?;
+                        }
+                    }
+                    if let (Some(first_chunk), Some(last_chunk)) =
+                        (batch.chunks.first(), batch.chunks.last())
+                    {
+                        let range = ChunkBlockRange {
+                            start_block_number: first_chunk.start_block_number,
+                            end_block_number: last_chunk.end_block_number,
+                        };
+                        tx.put::<BatchBlockRanges>(batch_index, range)?;
+                    } else {
+                        tx.delete::<BatchBlockRanges>(batch_index, None)?;
+                    }
+                    tx.put::<BatchChunkRanges>(batch_index, ChunkRanges(batch.chunks))?;
+                    for block in batch.blocks {
+                        tx.put::<BlockContexts>(block.number, block)?;
+                    }
+                    tx.put::<BatchL1MessagesPopped>(batch_index, batch.total_l1_messages_popped)?;
+                    tx.put::<BatchBlocks>(batch_index, batch.provenance.block_number)?;
+                    let status = BatchStatus::recommitted(
+                        get_batch_status(&tx, batch_index)?,
+                        batch_index,
+                        batch.provenance,
+                    );
+                    tx.put::<BatchStatuses>(batch_index, status)?;
+
+                    let (first, last) = get_committed_batch_bounds(&tx)?
+                        .map_or((batch_index, batch_index), |(first, last)| {
+                            (first.min(batch_index), last.max(batch_index))
+                        });
+                    tx.put::<Metadata>(FIRST_COMMITTED_BATCH_INDEX_KEY.to_string(), first)?;
+                    tx.put::<Metadata>(LAST_COMMITTED_BATCH_INDEX_KEY.to_string(), last)?;
+                }
+                BatchEvent::Finalized {
+                    batch_index,
+                    roots,
+                    provenance,
+                } => {
+                    tx.put::<BatchFinalizedRoots>(batch_index, roots)?;
+                    let last_finalized = get_last_finalized_batch_index(&tx)?;
+                    let from = match last_finalized {
+                        Some(last_finalized) => last_finalized + 1,
+                        None => {
+                            get_committed_batch_bounds(&tx)?.map_or(batch_index, |(first, _)| first)
+                        }
+                    };
+                    for index in from..=batch_index {
+                        if let Some(mut status) = get_batch_status(&tx, index)? {
+                            status.finalize(provenance.clone());
+                            tx.put::<BatchStatuses>(index, status)?;
+                        }
+                    }
+                    if last_finalized < Some(batch_index) {
+                        tx.put::<Metadata>(
+                            LAST_FINALIZED_BATCH_INDEX_KEY.to_string(),
+                            batch_index,
+                        )?;
+                    }
+                }
+                BatchEvent::Reverted {
+                    batch_index,
+                    provenance,
+                } => {
+                    if let Some(mut status) = get_batch_status(&tx, batch_index)? {
+                        status.revert(provenance);
+                        tx.put::<BatchStatuses>(batch_index, status)?;
+                    }
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn prune(&self, config: &PruneConfig) -> Result<PruneStats> {
+        let tx = self.db.tx_mut()?;
+        let mut stats = PruneStats::default();
+        let Some(last_finalized) = get_last_finalized_batch_index(&tx)? else {
+            return Ok(stats);
+        };
+
+        // Messages and chunk ranges are keyed by index, so the ones to prune are always the
+        // first entries of their table.
+        let popped = if config.l1_messages {
+            get_batch_total_l1_messages_popped(&tx, last_finalized)?
+        } else {
+            None
+        };
+        if let Some(popped) = popped {
+            let mut cursor = tx.cursor_write::<L1Messages>()?;
+            while let Some((queue_index, _)) = cursor.first()? {
+                if queue_index >= popped || stats.l1_messages == MAX_PRUNED_PER_RUN {
+                    break;
+                }
+                cursor.delete_current()?;
+                tx.delete::<L1MessageBlocks>(queue_index, None)?;
+                stats.l1_messages += 1;
+            }
+        }
+        if let Some(keep_from) = config.first_kept_batch(last_finalized) {
+            let mut cursor = tx.cursor_write::<BatchChunkRanges>()?;
+            while let Some((batch_index, _)) = cursor.first()? {
+                if batch_index >= keep_from || stats.batches == MAX_PRUNED_PER_RUN {
+                    break;
+                }
+                cursor.delete_current()?;
+                stats.batches += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(stats)
+    }
+}
+
+/// Storage that lives only as long as the process, for tests and short-lived runs that don't
+/// need a database on disk.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    state: RwLock<MemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    cursors: Cursors,
+    /// Messages with the L1 block they were emitted in, by queue index.
+    l1_messages: BTreeMap<u64, (L1MessageTx, u64)>,
+    /// Batches with the total L1 messages popped and their lifecycle, by batch index. The
+    /// batch is `None` if it was committed without chunks, it has no block range then.
+    batches: BTreeMap<u64, (Option<Batch>, u64, BatchStatus)>,
+    finalized_roots: BTreeMap<u64, FinalizedRoots>,
+    /// Block contexts by L2 block number.
+    block_contexts: BTreeMap<u64, BlockContext>,
+}
+
+impl Storage for MemoryStorage {
+    fn cursors(&self) -> Result<Cursors> {
+        Ok(self.state.read().unwrap().cursors.clone())
+    }
+
+    fn l1_message(&self, queue_index: u64) -> Result<Option<L1MessageTx>> {
+        let state = self.state.read().unwrap();
+        Ok(state
+            .l1_messages
+            .get(&queue_index)
+            .map(|(message, _)| message.clone()))
+    }
+
+    fn first_l1_message_index(&self) -> Result<Option<u64>> {
+        let state = self.state.read().unwrap();
+        Ok(state.l1_messages.keys().next().copied())
+    }
+
+    fn l1_messages_in_range(&self, from: u64, to: u64) -> Result<Vec<L1MessageTx>> {
+        let state = self.state.read().unwrap();
+        Ok(state
+            .l1_messages
+            .range(from..=to)
+            .map(|(_, (message, _))| message.clone())
+            .collect())
+    }
+
+    fn l1_messages_count_in_range(&self, from: u64, to: u64) -> Result<u64> {
+        if from > to {
+            return Ok(0);
+        }
+        let state = self.state.read().unwrap();
+        Ok(state.l1_messages.range(from..=to).count() as u64)
+    }
+
+    fn batch(&self, batch_index: u64) -> Result<Option<Batch>> {
+        let state = self.state.read().unwrap();
+        Ok(state
+            .batches
+            .get(&batch_index)
+            .and_then(|(batch, _, _)| batch.clone()))
+    }
+
+    fn batch_for_block(&self, l2_block_number: u64) -> Result<Option<Batch>> {
+        let state = self.state.read().unwrap();
+        Ok(state
+            .batches
+            .values()
+            .filter_map(|(batch, _, _)| batch.as_ref())
+            .find(|batch| (batch.first_block..=batch.last_block).contains(&l2_block_number))
+            .cloned())
+    }
+
+    fn block_context(&self, l2_block_number: u64) -> Result<Option<BlockContext>> {
+        let state = self.state.read().unwrap();
+        Ok(state.block_contexts.get(&l2_block_number).cloned())
+    }
+
+    fn batch_total_l1_messages_popped(&self, batch_index: u64) -> Result<Option<u64>> {
+        let state = self.state.read().unwrap();
+        Ok(state
+            .batches
+            .get(&batch_index)
+            .map(|(_, popped, _)| *popped))
+    }
+
+    fn commit_l1_messages(&self, messages: Vec<(L1MessageTx, u64)>, last_block: u64) -> Result<()> {
+        let mut state = self.state.write().unwrap();
+        state.cursors.last_synced_l1_block = Some(last_block);
+        for (message, l1_block_number) in messages {
+            let queue_index = message.queue_index;
+            state
+                .l1_messages
+                .insert(queue_index, (message, l1_block_number));
+            state.cursors.last_l1_message_index = Some(queue_index);
+        }
+        Ok(())
+    }
+
+    fn batch_status(&self, batch_index: u64) -> Result<Option<BatchStatus>> {
+        let state = self.state.read().unwrap();
+        Ok(state
+            .batches
+            .get(&batch_index)
+            .map(|(_, _, status)| status.clone()))
+    }
+
+    fn unfinalized_batches(&self, committed_before: u64) -> Result<Vec<BatchStatus>> {
+        let state = self.state.read().unwrap();
+        let from = state
+            .cursors
+            .last_finalized_batch_index
+            .map_or(0, |last_finalized| last_finalized + 1);
+        Ok(state
+            .batches
+            .range(from..)
+            .map(|(_, (_, _, status))| status)
+            .filter(|status| {
+                status.state == BatchState::Committed
+                    && status.committed.timestamp < committed_before
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn latest_finalized_roots(&self) -> Result<Option<FinalizedBatchRoots>> {
+        let state = self.state.read().unwrap();
+        let Some((batch_index, roots)) = state.finalized_roots.last_key_value() else {
+            return Ok(None);
+        };
+        Ok(state
+            .batches
+            .get(batch_index)
+            .and_then(|(batch, _, _)| batch.as_ref())
+            .map(|batch| FinalizedBatchRoots {
+                batch_index: *batch_index,
+                last_block: batch.last_block,
+                roots: *roots,
+            }))
+    }
+
+    fn commit_batch_events(&self, events: Vec<BatchEvent>, last_block: u64) -> Result<()> {
+        let mut state = self.state.write().unwrap();
+        state.cursors.rollup_last_synced_l1_block = Some(last_block);
+        for event in events {
+            match event {
+                BatchEvent::Committed(committed) => {
+                    let batch_index = committed.batch_index;
+                    let batch = Batch::from_chunks(batch_index, committed.chunks);
+                    let previous_range = state
+                        .batches
+                        .get(&batch_index)
+                        .and_then(|(previous, _, _)| previous.as_ref())
+                        .map(|previous| previous.first_block..=previous.last_block);
+                    if let Some(previous_range) = previous_range {
+                        state
+                            .block_contexts
+                            .retain(|l2_block_number, _| !previous_range.contains(l2_block_number));
+                    }
+                    for block in committed.blocks {
+                        state.block_contexts.insert(block.number, block);
+                    }
+                    let status = BatchStatus::recommitted(
+                        state
+                            .batches
+                            .get(&batch_index)
+                            .map(|(_, _, status)| status.clone()),
+                        batch_index,
+                        committed.provenance,
+                    );
+                    state.batches.insert(
+                        batch_index,
+                        (batch, committed.total_l1_messages_popped, status),
+                    );
+                    let cursors = &mut state.cursors;
+                    cursors.first_committed_batch_index = Some(
+                        cursors
+                            .first_committed_batch_index
+                            .map_or(batch_index, |first| first.min(batch_index)),
+                    );
+                    cursors.last_committed_batch_index = Some(
+                        cursors
+                            .last_committed_batch_index
+                            .map_or(batch_index, |last| last.max(batch_index)),
+                    );
+                }
+                BatchEvent::Finalized {
+                    batch_index,
+                    roots,
+                    provenance,
+                } => {
+                    state.finalized_roots.insert(batch_index, roots);
+                    let from = state
+                        .cursors
+                        .last_finalized_batch_index
+                        .map_or(0, |last_finalized| last_finalized + 1);
+                    if from <= batch_index {
+                        for (_, (_, _, status)) in state.batches.range_mut(from..=batch_index) {
+                            status.finalize(provenance.clone());
+                        }
+                    }
+                    let cursors = &mut state.cursors;
+                    cursors.last_finalized_batch_index =
+                        cursors.last_finalized_batch_index.max(Some(batch_index));
+                }
+                BatchEvent::Reverted {
+                    batch_index,
+                    provenance,
+                } => {
+                    if let Some((_, _, status)) = state.batches.get_mut(&batch_index) {
+                        status.revert(provenance);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn prune(&self, config: &PruneConfig) -> Result<PruneStats> {
+        let mut state = self.state.write().unwrap();
+        let mut stats = PruneStats::default();
+        let Some(last_finalized) = state.cursors.last_finalized_batch_index else {
+            return Ok(stats);
+        };
+
+        let popped = if config.l1_messages {
+            state
+                .batches
+                .get(&last_finalized)
+                .map(|(_, popped, _)| *popped)
+        } else {
+            None
+        };
+        if let Some(popped) = popped {
+            let kept = state.l1_messages.split_off(&popped);
+            stats.l1_messages = state.l1_messages.len() as u64;
+            state.l1_messages = kept;
+        }
+        if let Some(keep_from) = config.first_kept_batch(last_finalized) {
+            for (_, (batch, _, _)) in state.batches.range_mut(..keep_from) {
+                if let Some(batch) = batch.as_mut().filter(|batch| !batch.chunks.is_empty()) {
+                    batch.chunks.clear();
+                    stats.batches += 1;
+                }
+            }
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ethers::types::H256;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        db::init_db,
+        test_utils::{chunk, committed, message, provenance},
+    };
+
+    fn roots(seed: u8) -> FinalizedRoots {
+        FinalizedRoots {
+            state_root: H256::repeat_byte(seed),
+            withdraw_root: H256::repeat_byte(seed + 1),
+        }
+    }
+
+    /// Messages 0 to 4, batches 0 to 3 of which batch 2 has no chunks and batch 3 is reverted,
+    /// and batch 1 finalized.
+    fn sync(storage: &dyn Storage) {
+        storage
+            .commit_l1_messages(
+                (0..5)
+                    .map(|queue_index| (message(queue_index), 100 + queue_index))
+                    .collect(),
+                110,
+            )
+            .unwrap();
+        storage
+            .commit_batch_events(
+                vec![
+                    committed(0, vec![chunk(0, 4), chunk(5, 9)], 0, 2, 111),
+                    committed(1, vec![chunk(10, 14)], 2, 4, 111),
+                ],
+                111,
+            )
+            .unwrap();
+        storage
+            .commit_batch_events(
+                vec![
+                    committed(2, vec![], 4, 4, 112),
+                    committed(3, vec![chunk(15, 19)], 4, 5, 112),
+                    BatchEvent::Reverted {
+                        batch_index: 3,
+                        provenance: provenance(113),
+                    },
+                    BatchEvent::Finalized {
+                        batch_index: 1,
+                        roots: roots(1),
+                        provenance: provenance(114),
+                    },
+                ],
+                120,
+            )
+            .unwrap();
+    }
+
+    /// Continues `sync` with a batch committed below the last finalized one and the reverted
+    /// batch 3 committed again without chunks.
+    fn sync_out_of_order(storage: &dyn Storage) {
+        storage
+            .commit_batch_events(
+                vec![
+                    committed(3, vec![], 5, 5, 121),
+                    committed(5, vec![chunk(21, 22)], 5, 5, 121),
+                    BatchEvent::Finalized {
+                        batch_index: 5,
+                        roots: roots(5),
+                        provenance: provenance(122),
+                    },
+                    committed(4, vec![chunk(20, 20)], 5, 5, 123),
+                    committed(6, vec![chunk(23, 24)], 5, 5, 123),
+                    BatchEvent::Finalized {
+                        batch_index: 6,
+                        roots: roots(6),
+                        provenance: provenance(124),
+                    },
+                ],
+                130,
+            )
+            .unwrap();
+    }
+
+    /// Everything the services and the RPC server can read.
+    fn snapshot(storage: &dyn Storage) -> Value {
+        let batches: Vec<_> = (0..7)
+            .map(|batch_index| {
+                json!({
+                    "batch": storage.batch(batch_index).unwrap(),
+                    "status": storage.batch_status(batch_index).unwrap(),
+                    "popped": storage.batch_total_l1_messages_popped(batch_index).unwrap(),
+                })
+            })
+            .collect();
+        let blocks: Vec<_> = (0..25)
+            .map(|l2_block_number| {
+                json!({
+                    "batch": storage
+                        .batch_for_block(l2_block_number)
+                        .unwrap()
+                        .map(|batch| batch.batch_index),
+                    "context": storage.block_context(l2_block_number).unwrap(),
+                    "finalizedRoots": storage.finalized_roots_for_block(l2_block_number).unwrap(),
+                })
+            })
+            .collect();
+        json!({
+            "cursors": storage.cursors().unwrap(),
+            "messages": storage.l1_messages_in_range(0, 10).unwrap(),
+            "firstMessage": storage.first_l1_message_index().unwrap(),
+            "messageCounts": [
+                storage.l1_messages_count_in_range(0, 10).unwrap(),
+                storage.l1_messages_count_in_range(3, 3).unwrap(),
+                storage.l1_messages_count_in_range(4, 3).unwrap(),
+            ],
+            "batches": batches,
+            "blocks": blocks,
+            "unfinalized": storage.unfinalized_batches(u64::MAX).unwrap(),
+        })
+    }
+
+    #[test]
+    fn memory_storage_keeps_batches_without_chunks() {
+        let storage = MemoryStorage::default();
+        sync(&storage);
+
+        assert!(storage.batch(2).unwrap().is_none());
+        assert_eq!(storage.batch_total_l1_messages_popped(2).unwrap(), Some(4));
+        assert_eq!(
+            storage.batch_status(2).unwrap().map(|status| status.state),
+            Some(BatchState::Committed)
+        );
+        let cursors = storage.cursors().unwrap();
+        assert_eq!(cursors.first_committed_batch_index, Some(0));
+        assert_eq!(cursors.last_committed_batch_index, Some(3));
+        assert_eq!(cursors.last_finalized_batch_index, Some(1));
+    }
+
+    #[test]
+    fn mdbx_and_memory_storage_agree() {
+        let datadir = TempDir::new().unwrap();
+        let mdbx = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        let memory = MemoryStorage::default();
+        sync(&mdbx);
+        sync(&memory);
+        assert_eq!(snapshot(&mdbx), snapshot(&memory));
+
+        sync_out_of_order(&mdbx);
+        sync_out_of_order(&memory);
+        let memory_snapshot = snapshot(&memory);
+        assert_eq!(snapshot(&mdbx), memory_snapshot);
+        // Batch 4 was committed below the last finalized batch, finalizing batch 6 skips it.
+        assert_eq!(
+            memory_snapshot["batches"][4]["status"]["state"],
+            "committed"
+        );
+        assert_eq!(
+            memory_snapshot["batches"][3]["status"]["state"],
+            "finalized"
+        );
+        // The range of batch 3 before the revert is gone.
+        assert!(memory_snapshot["blocks"][15]["batch"].is_null());
+    }
+
+    fn prune_config(l1_messages: bool, keep_finalized_batches: Option<u64>) -> PruneConfig {
+        PruneConfig {
+            l1_messages,
+            keep_finalized_batches,
+            interval: Duration::from_secs(600),
+        }
+    }
+
+    fn backends(datadir: &TempDir) -> [Box<dyn Storage>; 2] {
+        [
+            Box::new(MdbxStorage::new(init_db(datadir.path()).unwrap())),
+            Box::new(MemoryStorage::default()),
+        ]
+    }
+
+    #[test]
+    fn replayed_commit_keeps_the_batch_status() {
+        let datadir = TempDir::new().unwrap();
+        for storage in backends(&datadir) {
+            let state = |batch_index| storage.batch_status(batch_index).unwrap().unwrap().state;
+            storage
+                .commit_batch_events(
+                    vec![
+                        committed(0, vec![chunk(0, 4)], 0, 0, 111),
+                        committed(1, vec![chunk(5, 9)], 0, 0, 111),
+                        BatchEvent::Finalized {
+                            batch_index: 0,
+                            roots: roots(1),
+                            provenance: provenance(112),
+                        },
+                        BatchEvent::Reverted {
+                            batch_index: 1,
+                            provenance: provenance(112),
+                        },
+                    ],
+                    112,
+                )
+                .unwrap();
+
+            // The same commits synced again, e.g. after a restart.
+            storage
+                .commit_batch_events(
+                    vec![
+                        committed(0, vec![chunk(0, 4)], 0, 0, 111),
+                        committed(1, vec![chunk(5, 9)], 0, 0, 111),
+                    ],
+                    112,
+                )
+                .unwrap();
+            assert_eq!(state(0), BatchState::Finalized);
+            assert_eq!(
+                storage.batch_status(0).unwrap().unwrap().finalized,
+                Some(provenance(112))
+            );
+            // A reverted batch is committed again.
+            assert_eq!(state(1), BatchState::Committed);
+
+            // A new commit of the batch starts a new lifecycle.
+            storage
+                .commit_batch_events(
+                    vec![
+                        BatchEvent::Reverted {
+                            batch_index: 1,
+                            provenance: provenance(113),
+                        },
+                        committed(1, vec![chunk(5, 8)], 0, 0, 114),
+                    ],
+                    114,
+                )
+                .unwrap();
+            let status = storage.batch_status(1).unwrap().unwrap();
+            assert_eq!(status.state, BatchState::Committed);
+            assert_eq!(status.committed, provenance(114));
+            assert_eq!(status.reverted, None);
+        }
+    }
+
+    #[test]
+    fn prune_nothing_before_a_batch_is_finalized() {
+        let datadir = TempDir::new().unwrap();
+        for storage in backends(&datadir) {
+            storage
+                .commit_l1_messages(vec![(message(0), 100), (message(1), 101)], 110)
+                .unwrap();
+            storage
+                .commit_batch_events(vec![committed(0, vec![chunk(0, 4)], 0, 2, 111)], 111)
+                .unwrap();
+
+            let stats = storage.prune(&prune_config(true, Some(0))).unwrap();
+            assert_eq!((stats.l1_messages, stats.batches), (0, 0));
+            assert_eq!(storage.l1_messages_in_range(0, 1).unwrap().len(), 2);
+            assert_eq!(storage.batch(0).unwrap().unwrap().chunks, [chunk(0, 4)]);
+        }
+    }
+
+    #[test]
+    fn prune_every_finalized_batch() {
+        let datadir = TempDir::new().unwrap();
+        for storage in backends(&datadir) {
+            sync(storage.as_ref());
+
+            let stats = storage.prune(&prune_config(true, Some(0))).unwrap();
+            // Batch 1 is the last finalized one and popped the messages 0 to 3.
+            assert_eq!((stats.l1_messages, stats.batches), (4, 2));
+            assert!(storage.l1_message(3).unwrap().is_none());
+            assert!(storage.l1_message(4).unwrap().is_some());
+            assert_eq!(storage.l1_messages_count_in_range(0, 10).unwrap(), 1);
+            let batch = storage.batch(1).unwrap().unwrap();
+            assert!(batch.chunks.is_empty());
+            assert_eq!((batch.first_block, batch.last_block), (10, 14));
+            assert_eq!(storage.batch_for_block(12).unwrap().unwrap().batch_index, 1);
+            assert_eq!(storage.batch(3).unwrap().unwrap().chunks, [chunk(15, 19)]);
+
+            let stats = storage.prune(&prune_config(true, Some(0))).unwrap();
+            assert_eq!((stats.l1_messages, stats.batches), (0, 0));
+        }
+    }
+
+    #[test]
+    fn prune_stops_after_max_entries_per_run() {
+        let datadir = TempDir::new().unwrap();
+        let storage = MdbxStorage::new(init_db(datadir.path()).unwrap());
+        let total = MAX_PRUNED_PER_RUN + 10;
+        storage
+            .commit_l1_messages(
+                (0..total)
+                    .map(|queue_index| (message(queue_index), 100))
+                    .collect(),
+                110,
+            )
+            .unwrap();
+        storage
+            .commit_batch_events(
+                vec![
+                    committed(0, vec![chunk(0, 0)], 0, total, 111),
+                    BatchEvent::Finalized {
+                        batch_index: 0,
+                        roots: roots(1),
+                        provenance: provenance(112),
+                    },
+                ],
+                120,
+            )
+            .unwrap();
+
+        let config = prune_config(true, None);
+        assert_eq!(
+            storage.prune(&config).unwrap().l1_messages,
+            MAX_PRUNED_PER_RUN
+        );
+        assert!(storage
+            .l1_message(MAX_PRUNED_PER_RUN - 1)
+            .unwrap()
+            .is_none());
+        assert!(storage.l1_message(MAX_PRUNED_PER_RUN).unwrap().is_some());
+        assert_eq!(storage.prune(&config).unwrap().l1_messages, 10);
+        assert_eq!(storage.prune(&config).unwrap().l1_messages, 0);
+    }
+
+    #[test]
+    fn recommit_removes_contexts_of_dropped_blocks() {
+        let datadir = TempDir::new().unwrap();
+        for storage in backends(&datadir) {
+            storage
+                .commit_batch_events(
+                    vec![
+                        committed(1, vec![chunk(10, 14), chunk(15, 19)], 0, 0, 111),
+                        BatchEvent::Reverted {
+                            batch_index: 1,
+                            provenance: provenance(112),
+                        },
+                        committed(1, vec![chunk(10, 14)], 0, 0, 113),
+                    ],
+                    120,
+                )
+                .unwrap();
+
+            assert_eq!(storage.batch(1).unwrap().unwrap().last_block, 14);
+            assert!(storage.block_context(14).unwrap().is_some());
+            for l2_block_number in 15..=19 {
+                assert!(storage.block_context(l2_block_number).unwrap().is_none());
+            }
+        }
+    }
+}
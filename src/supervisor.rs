@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use tokio::{task::JoinSet, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+const DEFAULT_MAX_FAILURES: u32 = 5;
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// Consecutive failures after which a task is given up on and the node shuts down.
+    pub max_failures: u32,
+    /// Delay before the first restart, doubled after every consecutive failure.
+    pub initial_backoff: Duration,
+    /// Upper bound of the restart delay. A task that stays up for this long is considered
+    /// healthy again and its failure count is reset.
+    pub max_backoff: Duration,
+}
+
+impl SupervisorConfig {
+    pub fn from_env() -> Self {
+        let secs = |key: &str, default: Duration| {
+            env::var(key)
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .map_or(default, Duration::from_secs)
+        };
+        Self {
+            max_failures: env::var("SUPERVISOR_MAX_FAILURES")
+                .ok()
+                .and_then(|failures| failures.parse().ok())
+                .unwrap_or(DEFAULT_MAX_FAILURES),
+            initial_backoff: secs("SUPERVISOR_INITIAL_BACKOFF_SECS", DEFAULT_INITIAL_BACKOFF),
+            max_backoff: secs("SUPERVISOR_MAX_BACKOFF_SECS", DEFAULT_MAX_BACKOFF),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskHealth {
+    Running,
+    /// Waiting for the backoff to expire before restarting.
+    Restarting,
+    /// Exceeded the maximum number of consecutive failures.
+    Failed,
+    Stopped,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskState {
+    pub health: TaskHealth,
+    pub restarts: u32,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+/// Health of every supervised task, keyed by task name.
+#[derive(Debug, Clone, Default)]
+pub struct HealthRegistry {
+    tasks: Arc<RwLock<BTreeMap<&'static str, TaskState>>>,
+}
+
+impl HealthRegistry {
+    pub fn snapshot(&self) -> BTreeMap<&'static str, TaskState> {
+        self.tasks.read().unwrap().clone()
+    }
+
+    fn update(&self, name: &'static str, update: impl FnOnce(&mut TaskState)) {
+        let mut tasks = self.tasks.write().unwrap();
+        let state = tasks.entry(name).or_insert(TaskState {
+            health: TaskHealth::Running,
+            restarts: 0,
+            consecutive_failures: 0,
+            last_error: None,
+        });
+        update(state);
+    }
+}
+
+/// Runs every service as a managed task, restarting it with exponential backoff when it
+/// fails, panics or exits before shutdown. A task that keeps failing cancels `shutdown`.
+#[derive(Debug)]
+pub struct Supervisor {
+    config: SupervisorConfig,
+    shutdown: CancellationToken,
+    health: HealthRegistry,
+    tasks: JoinSet<Result<()>>,
+}
+
+impl Supervisor {
+    pub fn new(config: SupervisorConfig, shutdown: CancellationToken) -> Self {
+        info!("Supervisor config: {:?}", config);
+        Self {
+            config,
+            shutdown,
+            health: HealthRegistry::default(),
+            tasks: JoinSet::new(),
+        }
+    }
+
+    pub fn health(&self) -> HealthRegistry {
+        self.health.clone()
+    }
+
+    /// Spawns a task built by `factory`, which is called again for every restart.
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, factory: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let config = self.config.clone();
+        let shutdown = self.shutdown.clone();
+        let health = self.health.clone();
+
+        self.tasks.spawn(async move {
+            let mut backoff = config.initial_backoff;
+            loop {
+                health.update(name, |state| state.health = TaskHealth::Running);
+                info!("Starting task {}", name);
+                let started_at = Instant::now();
+
+                let error = match tokio::spawn(factory()).await {
+                    Ok(Ok(())) if shutdown.is_cancelled() => {
+                        health.update(name, |state| state.health = TaskHealth::Stopped);
+                        info!("Task {} stopped", name);
+                        return Ok(());
+                    }
+                    Ok(Ok(())) => "exited before shutdown".to_string(),
+                    Ok(Err(err)) => err.to_string(),
+                    Err(err) => err.to_string(),
+                };
+                if shutdown.is_cancelled() {
+                    health.update(name, |state| state.health = TaskHealth::Stopped);
+                    warn!("Task {} failed during shutdown: {}", name, error);
+                    return Ok(());
+                }
+
+                if started_at.elapsed() >= config.max_backoff {
+                    health.update(name, |state| state.consecutive_failures = 0);
+                    backoff = config.initial_backoff;
+                }
+                let mut failures = 0;
+                health.update(name, |state| {
+                    state.consecutive_failures += 1;
+                    state.last_error = Some(error.clone());
+                    failures = state.consecutive_failures;
+                });
+
+                if failures >= config.max_failures {
+                    health.update(name, |state| state.health = TaskHealth::Failed);
+                    error!(
+                        "Task {} failed {} times in a row: {}",
+                        name, failures, error
+                    );
+                    return Err(anyhow!(
+                        "task {} failed {} times: {}",
+                        name,
+                        failures,
+                        error
+                    ));
+                }
+
+                health.update(name, |state| {
+                    state.health = TaskHealth::Restarting;
+                    state.restarts += 1;
+                });
+                warn!(
+                    "Task {} failed: {}, restarting in {:?}",
+                    name, error, backoff
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.cancelled() => {
+                        health.update(name, |state| state.health = TaskHealth::Stopped);
+                        return Ok(());
+                    }
+                }
+                backoff = (backoff * 2).min(config.max_backoff);
+            }
+        });
+    }
+
+    /// Waits for every task to stop. If a task exhausted its restarts, all other tasks are
+    /// shut down and its error is returned.
+    pub async fn wait(mut self) -> Result<()> {
+        let mut result = Ok(());
+        while let Some(joined) = self.tasks.join_next().await {
+            let task_result = joined.map_err(|err| anyhow!(err)).and_then(|result| result);
+            if let Err(err) = task_result {
+                self.shutdown.cancel();
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use anyhow::bail;
+
+    use super::*;
+
+    fn config(max_failures: u32, initial_backoff: u64, max_backoff: u64) -> SupervisorConfig {
+        SupervisorConfig {
+            max_failures,
+            initial_backoff: Duration::from_secs(initial_backoff),
+            max_backoff: Duration::from_secs(max_backoff),
+        }
+    }
+
+    /// Supervises a task whose attempt `n` fails after running for `uptimes[n]` seconds, and
+    /// returns the start time of every attempt relative to the first one.
+    fn supervise(supervisor: &mut Supervisor, uptimes: &'static [u64]) -> Arc<Mutex<Vec<Instant>>> {
+        let starts = Arc::new(Mutex::new(vec![]));
+        let task_starts = starts.clone();
+        supervisor.spawn("task", move || {
+            let starts = task_starts.clone();
+            async move {
+                let attempt = {
+                    let mut starts = starts.lock().unwrap();
+                    starts.push(Instant::now());
+                    starts.len() - 1
+                };
+                tokio::time::sleep(Duration::from_secs(uptimes[attempt])).await;
+                bail!("attempt {} failed", attempt)
+            }
+        });
+        starts
+    }
+
+    fn offsets(starts: &Mutex<Vec<Instant>>) -> Vec<u64> {
+        let starts = starts.lock().unwrap();
+        starts
+            .iter()
+            .map(|start| start.duration_since(starts[0]).as_secs())
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_with_exponential_backoff_until_failed() {
+        let shutdown = CancellationToken::new();
+        let mut supervisor = Supervisor::new(config(4, 1, 3), shutdown.clone());
+        let health = supervisor.health();
+        let starts = supervise(&mut supervisor, &[0, 0, 0, 0]);
+
+        let err = supervisor.wait().await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "task task failed 4 times: attempt 3 failed"
+        );
+        // Backoffs of 1, 2 and 3 seconds, the last one capped by `max_backoff`.
+        assert_eq!(offsets(&starts), vec![0, 1, 3, 6]);
+        let state = health.snapshot()["task"].clone();
+        assert_eq!(state.health, TaskHealth::Failed);
+        assert_eq!(state.restarts, 3);
+        assert_eq!(state.consecutive_failures, 4);
+        assert_eq!(state.last_error.as_deref(), Some("attempt 3 failed"));
+        assert!(shutdown.is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn resets_failures_after_max_backoff_of_uptime() {
+        let shutdown = CancellationToken::new();
+        let mut supervisor = Supervisor::new(config(2, 1, 4), shutdown.clone());
+        let health = supervisor.health();
+        // The second attempt stays up for `max_backoff`, so its failure counts as the first one
+        // again and the backoff goes back to `initial_backoff`.
+        let starts = supervise(&mut supervisor, &[0, 4, 0]);
+
+        supervisor.wait().await.unwrap_err();
+
+        assert_eq!(offsets(&starts), vec![0, 1, 6]);
+        let state = health.snapshot()["task"].clone();
+        assert_eq!(state.health, TaskHealth::Failed);
+        assert_eq!(state.restarts, 2);
+        assert_eq!(state.consecutive_failures, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_restarting_on_shutdown() {
+        let shutdown = CancellationToken::new();
+        let mut supervisor = Supervisor::new(config(5, 10, 60), shutdown.clone());
+        let health = supervisor.health();
+        let starts = supervise(&mut supervisor, &[0, 0]);
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(health.snapshot()["task"].health, TaskHealth::Restarting);
+        shutdown.cancel();
+        supervisor.wait().await.unwrap();
+
+        assert_eq!(offsets(&starts), vec![0]);
+        assert_eq!(health.snapshot()["task"].health, TaskHealth::Stopped);
+    }
+}
@@ -1,192 +1,228 @@
-use std::{cmp::min, env, str::FromStr, sync::Arc};
+use std::{env, str::FromStr, sync::Arc};
 
 use alloy_primitives::Address;
-use alloy_rlp::RlpEncodable;
+use alloy_rlp::{RlpDecodable, RlpEncodable};
 use alloy_sol_types::{private::FixedBytes, SolEventInterface};
-use ethers::{
-    providers::{Http, Middleware, Provider},
-    types::H160,
-};
-use reth_db::{
-    database::Database,
-    mdbx::{tx::Tx, RW},
-    tables,
-    transaction::{DbTx, DbTxMut},
-    DatabaseEnv,
-};
-use tokio::sync::oneshot;
-use tracing::info;
-
-use crate::L1MessageQueue::L1MessageQueueEvents;
+use ethers::types::{TransactionReceipt, H160};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::{status::SyncStatus, storage::Storage, L1MessageQueue::L1MessageQueueEvents};
 
 /*
  * 1. Fetch the last synced block (LSB)
- * 2. Set from from to LSB
- * 3. Set To to Latest confirmed block
- * 4. Fetch Messages/Events from `from` to `to` using jumps of DEFAULT_SIZE
- * 5. Update the LSB after each successful fetch
- * 6.
+ * 2. The L1 ingestor fetches receipts from the lowest LSB of all consumers to the latest block
+ * 3. Filter the QueueTransaction events out of each block's receipts
+ * 4. Store the messages of every fetched range that goes beyond our LSB
+ * 5. Update the LSB after each successful range
  */
 
-#[derive(Clone, Debug, RlpEncodable)]
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct L1MessageTx {
-    queue_index: u64,
-    gas: alloy_primitives::Uint<256, 4>,
-    to: Address,
-    value: alloy_primitives::Uint<256, 4>,
-    data: alloy_primitives::Bytes,
-    sender: Address,
+    pub queue_index: u64,
+    pub gas: alloy_primitives::Uint<256, 4>,
+    pub to: Address,
+    pub value: alloy_primitives::Uint<256, 4>,
+    pub data: alloy_primitives::Bytes,
+    pub sender: Address,
 }
 
 #[derive(Debug)]
 pub struct SyncService {
-    db: Arc<DatabaseEnv>,
-    last_synced_block: Option<u64>,
-    provider: Provider<Http>,
+    storage: Arc<dyn Storage>,
+    status: Arc<SyncStatus>,
 }
 
 impl SyncService {
-    pub fn new(db: Arc<DatabaseEnv>, provider: Provider<Http>) -> Self {
-        let tx = db.tx_mut().expect("Could not create transaction");
-        let mut last_synced_block = tx
-            .get::<tables::SyncL1LastBlockNumber>("LastSyncedL1BlockNumber".to_string())
-            .unwrap();
+    pub fn new(storage: Arc<dyn Storage>, status: Arc<SyncStatus>) -> Self {
+        let last_synced_block = storage
+            .cursors()
+            .expect("Could not read the sync cursors")
+            .last_synced_l1_block;
 
         // Put the genesis block if the last synced block is None
         info!("Last synced block: {:?}", last_synced_block);
-        if last_synced_block.is_none() {
-            // last_synced_block = Some(18306000);
-            last_synced_block = Some(19972300);
-            tx.put::<tables::SyncL1LastBlockNumber>(
-                "LastSyncedL1BlockNumber".to_string(),
-                last_synced_block.unwrap(),
-            )
-            .unwrap();
-        }
-        Self {
-            db,
-            last_synced_block,
-            provider,
-        }
+        let last_synced_block = match last_synced_block {
+            Some(last_synced_block) => last_synced_block,
+            None => {
+                // let genesis_block = 18306000;
+                let genesis_block = 19972300;
+                storage
+                    .commit_l1_messages(vec![], genesis_block)
+                    .expect("Could not store the genesis block");
+                genesis_block
+            }
+        };
+        status.set_l1_message_synced_block(last_synced_block);
+        Self { storage, status }
     }
 
-    pub async fn start(&self, terminate_rx: oneshot::Receiver<()>) {
-        info!("Sync service started");
-        let mut tx = self.db.tx_mut().unwrap();
-        loop {
-            tokio::select! {
-                _ = self.fetch_messages(&mut tx) => {
-                    break;
-                }
-                _ = terminate_rx => {
-                    info!("Received a message to stop the sync service");
-                    break;
-                }
+    pub fn last_synced_block(&self) -> u64 {
+        self.storage
+            .cursors()
+            .expect("Could not read the sync cursors")
+            .last_synced_l1_block
+            .expect("Last synced block is always set on startup")
+    }
 
-            }
+    /// Stores the messages of a fetched range, along with the L1 block each one was emitted in,
+    /// and moves the last synced block to `last_block`. Ranges we already synced past are
+    /// ignored and so are the logs of blocks up to our last synced block, so the ingestor may
+    /// hand us ranges starting below it.
+    pub fn handle_logs(&self, logs: Vec<(L1MessageQueueEvents, u64)>, last_block: u64) {
+        let last_synced_block = self.last_synced_block();
+        if last_block <= last_synced_block {
+            return;
         }
-        tx.commit().expect("Could not commit transaction");
-        info!("Sync service stopped");
-    }
-    async fn fetch_messages(&self, tx_mut: &mut Tx<RW>) {
-        let from = self.last_synced_block.unwrap();
-        let to = self.provider.get_block_number().await.unwrap().as_u64();
-        info!(
-            "-------------------Fetching messages from {} to {}",
-            from, to
-        );
+        debug!("Storing {} logs up to block {}", logs.len(), last_block);
 
-        for block_number in (from..to).step_by(100) {
-            info!("###Block number: {:?}", block_number);
-            let (logs, last_queried_block) = self
-                .get_filtered_logs(block_number, min(to, block_number + 100))
-                .await;
-            info!("***Logs: {:?}", logs.len());
-            info!("***Block number: {:?}", block_number);
-
-            tx_mut
-                .put::<tables::SyncL1LastBlockNumber>(
-                    "LastSyncedL1BlockNumber".to_string(),
-                    last_queried_block,
-                )
-                .unwrap();
-            for event in logs {
-                match event {
-                    L1MessageQueueEvents::QueueTransaction(tx) => {
-                        let l1_msg_tx = L1MessageTx {
-                            queue_index: tx.queueIndex,
-                            gas: tx.gasLimit,
-                            to: tx.target,
-                            value: tx.value,
-                            data: tx.data,
-                            sender: tx.sender,
-                        };
-
-                        let rlp_encoded_l1_msg_tx = alloy_rlp::encode(&l1_msg_tx);
-                        let _ = tx_mut.put::<tables::SyncL1MessageQueue>(
-                            format!("L1{}", l1_msg_tx.queue_index),
-                            rlp_encoded_l1_msg_tx,
-                        );
-                    }
-                    _ => {}
+        let mut messages = vec![];
+        for (event, l1_block_number) in logs {
+            if l1_block_number <= last_synced_block {
+                continue;
+            }
+            match event {
+                L1MessageQueueEvents::QueueTransaction(tx) => {
+                    let l1_msg_tx = L1MessageTx {
+                        queue_index: tx.queueIndex,
+                        gas: tx.gasLimit,
+                        to: tx.target,
+                        value: tx.value,
+                        data: tx.data,
+                        sender: tx.sender,
+                    };
+                    messages.push((l1_msg_tx, l1_block_number));
                 }
+                _ => {}
             }
         }
+        let indexed_messages = messages.len() as u64;
+        self.storage
+            .commit_l1_messages(messages, last_block)
+            .expect("Could not commit L1 messages");
+        self.status.set_l1_message_synced_block(last_block);
+        self.status.add_l1_messages_indexed(indexed_messages);
+        counter!(
+            "scroll_follower_l1_messages_indexed_total",
+            indexed_messages
+        );
     }
 
-    pub async fn get_filtered_logs(&self, from: u64, to: u64) -> (Vec<L1MessageQueueEvents>, u64) {
-        let mut filtered_logs = vec![];
+    /// Extracts the `QueueTransaction` events from the receipts of a single block.
+    pub fn filter_logs(&self, receipts: &[TransactionReceipt]) -> Vec<L1MessageQueueEvents> {
         let l1_scroll_messenger = env::var("L1_SCROLL_MESSENGER").unwrap();
-        info!("Fetching logs from {} to {}", from, to);
-        for block_number in (from..to + 1).step_by(1) {
-            info!("Block number: {:?}\n", block_number);
-            let receipts = self.provider.get_block_receipts(block_number).await;
-            if receipts.is_err() {
-                info!(
-                    "Error fetching receipts for block number: {:?}",
-                    block_number
-                );
-
-                return (filtered_logs, block_number - 1);
-            }
-            let receipts = receipts.unwrap();
+        receipts
+            .iter()
+            .filter(|receipt| receipt.to == Some(H160::from_str(&l1_scroll_messenger).unwrap()))
+            .flat_map(|receipt| receipt.logs.iter().map(move |log| (receipt, log)))
+            .filter_map(|(_receipt, log)| {
+                let topics: Vec<_> = log
+                    .topics
+                    .iter()
+                    .map(|topic| FixedBytes::new(topic.to_fixed_bytes()))
+                    .collect();
+                L1MessageQueueEvents::decode_raw_log(&topics, &log.data, true)
+                    .ok()
+                    .map(|event| (event))
+            })
+            .filter(|event| match event {
+                L1MessageQueueEvents::QueueTransaction(_) => true,
+                _ => false,
+            })
+            .collect()
+    }
+}
 
-            info!(
-                "block number: {:?},, receipts length: {:?}",
-                block_number,
-                receipts.len()
-            );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{storage::MemoryStorage, test_utils::message, L1MessageQueue::QueueTransaction};
+
+    const GENESIS_BLOCK: u64 = 19972300;
+
+    fn queue_transaction(queue_index: u64) -> L1MessageQueueEvents {
+        let message = message(queue_index);
+        L1MessageQueueEvents::QueueTransaction(QueueTransaction {
+            sender: message.sender,
+            target: message.to,
+            value: message.value,
+            queueIndex: message.queue_index,
+            gasLimit: message.gas,
+            data: message.data,
+        })
+    }
+
+    #[test]
+    fn starts_at_genesis_block() {
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let sync_service = SyncService::new(storage.clone(), status.clone());
+
+        assert_eq!(sync_service.last_synced_block(), GENESIS_BLOCK);
+        assert_eq!(status.l1_message_synced_block(), GENESIS_BLOCK);
+        assert_eq!(
+            storage.cursors().unwrap().last_synced_l1_block,
+            Some(GENESIS_BLOCK)
+        );
+    }
 
-            let new_logs: Vec<L1MessageQueueEvents> = receipts
+    #[test]
+    fn stores_messages_of_new_ranges() {
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let sync_service = SyncService::new(storage.clone(), status.clone());
+
+        sync_service.handle_logs(
+            vec![
+                (queue_transaction(0), GENESIS_BLOCK + 1),
+                (queue_transaction(1), GENESIS_BLOCK + 3),
+            ],
+            GENESIS_BLOCK + 10,
+        );
+        assert_eq!(sync_service.last_synced_block(), GENESIS_BLOCK + 10);
+        assert_eq!(status.l1_messages_indexed(), 2);
+        assert_eq!(storage.cursors().unwrap().last_l1_message_index, Some(1));
+        let stored = storage.l1_messages_in_range(0, 1).unwrap();
+        assert_eq!(
+            stored.iter().map(alloy_rlp::encode).collect::<Vec<_>>(),
+            [message(0), message(1)]
                 .iter()
-                .filter(|receipt| receipt.to == Some(H160::from_str(&l1_scroll_messenger).unwrap()))
-                .flat_map(|receipt| receipt.logs.iter().map(move |log| (receipt, log)))
-                .filter_map(|(_receipt, log)| {
-                    let topics: Vec<_> = log
-                        .topics
-                        .iter()
-                        .map(|topic| FixedBytes::new(topic.to_fixed_bytes()))
-                        .collect();
-                    L1MessageQueueEvents::decode_raw_log(&topics, &log.data, true)
-                        .ok()
-                        .map(|event| (event))
-                })
-                .filter(|event| match event {
-                    L1MessageQueueEvents::QueueTransaction(_) => true,
-                    _ => false,
-                })
-                .collect();
-
-            if new_logs.len() > 0 {
-                info!(
-                    "New logs: {:?}, block_number {:?}",
-                    new_logs.len(),
-                    block_number
-                );
-                filtered_logs.extend(new_logs);
-            }
-        }
+                .map(alloy_rlp::encode)
+                .collect::<Vec<_>>()
+        );
+
+        // A range the ingestor hands out again after a restart is not stored twice.
+        sync_service.handle_logs(
+            vec![(queue_transaction(5), GENESIS_BLOCK + 5)],
+            GENESIS_BLOCK + 5,
+        );
+        assert_eq!(sync_service.last_synced_block(), GENESIS_BLOCK + 10);
+        assert!(storage.l1_message(5).unwrap().is_none());
+        assert_eq!(status.l1_messages_indexed(), 2);
+    }
 
-        return (filtered_logs, to);
+    #[test]
+    fn skips_logs_of_synced_blocks() {
+        let storage = Arc::new(MemoryStorage::default());
+        let status = Arc::new(SyncStatus::default());
+        let sync_service = SyncService::new(storage.clone(), status.clone());
+        sync_service.handle_logs(
+            vec![(queue_transaction(0), GENESIS_BLOCK + 10)],
+            GENESIS_BLOCK + 10,
+        );
+
+        // The rollup consumer was behind, so the range starts below our last synced block.
+        sync_service.handle_logs(
+            vec![
+                (queue_transaction(0), GENESIS_BLOCK + 10),
+                (queue_transaction(1), GENESIS_BLOCK + 11),
+            ],
+            GENESIS_BLOCK + 20,
+        );
+        assert_eq!(sync_service.last_synced_block(), GENESIS_BLOCK + 20);
+        assert_eq!(status.l1_messages_indexed(), 2);
+        assert_eq!(storage.cursors().unwrap().last_l1_message_index, Some(1));
     }
 }
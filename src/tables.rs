@@ -0,0 +1,178 @@
+use bytes::BufMut;
+use ethers::utils::rlp;
+use reth_db::{
+    table::{Compress, Decompress, Table},
+    DatabaseError,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch_status::{BatchStatus, FinalizedRoots},
+    rollup_sync_service_util::{BlockContext, ChunkBlockRange},
+    sync_service::L1MessageTx,
+};
+
+/// Declares a follower table. Integer keys are stored big-endian, so cursors walk the entries
+/// in index order.
+macro_rules! table {
+    ($(#[$docs:meta])+ $name:ident => $key:ty => $value:ty) => {
+        $(#[$docs])+
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $name;
+
+        impl Table for $name {
+            const NAME: &'static str = stringify!($name);
+            type Key = $key;
+            type Value = $value;
+        }
+    };
+}
+
+table!(
+    /// Sync cursors, index bounds and the schema version, keyed by the constants in `db`.
+    Metadata => String => u64
+);
+table!(
+    /// L1 messages by queue index.
+    L1Messages => u64 => L1MessageTx
+);
+table!(
+    /// L1 block each message was emitted in, by queue index.
+    L1MessageBlocks => u64 => u64
+);
+table!(
+    /// Chunk block ranges of each committed batch, by batch index.
+    BatchChunkRanges => u64 => ChunkRanges
+);
+table!(
+    /// L1 block each batch was committed in, by batch index.
+    BatchBlocks => u64 => u64
+);
+table!(
+    /// L2 block range of each committed batch, by batch index. Unlike the chunk ranges it is
+    /// never pruned, so blocks can always be mapped to their batch.
+    BatchBlockRanges => u64 => ChunkBlockRange
+);
+table!(
+    /// Total number of L1 messages popped up to and including each batch, by batch index.
+    BatchL1MessagesPopped => u64 => u64
+);
+table!(
+    /// Lifecycle state of each batch with the L1 block and transaction of every transition,
+    /// by batch index.
+    BatchStatuses => u64 => BatchStatus
+);
+table!(
+    /// State root and withdraw root of each finalized batch whose `FinalizeBatch` event carried
+    /// them, by batch index.
+    BatchFinalizedRoots => u64 => FinalizedRoots
+);
+table!(
+    /// Context of each L2 block as encoded in the chunk that commits it, by L2 block number.
+    BlockContexts => u64 => BlockContext
+);
+
+/// Names of every follower table, created when the database is opened for writing.
+pub const TABLES: [&str; 10] = [
+    Metadata::NAME,
+    L1Messages::NAME,
+    L1MessageBlocks::NAME,
+    BatchChunkRanges::NAME,
+    BatchBlocks::NAME,
+    BatchBlockRanges::NAME,
+    BatchL1MessagesPopped::NAME,
+    BatchStatuses::NAME,
+    BatchFinalizedRoots::NAME,
+    BlockContexts::NAME,
+];
+
+impl Compress for L1MessageTx {
+    type Compressed = Vec<u8>;
+
+    fn compress_to_buf<B: BufMut + AsMut<[u8]>>(self, buf: &mut B) {
+        buf.put_slice(&alloy_rlp::encode(&self));
+    }
+}
+
+impl Decompress for L1MessageTx {
+    fn decompress<B: AsRef<[u8]>>(value: B) -> Result<Self, DatabaseError> {
+        alloy_rlp::Decodable::decode(&mut value.as_ref()).map_err(|_| DatabaseError::Decode)
+    }
+}
+
+/// Chunk block ranges of a batch, stored as an RLP list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkRanges(pub Vec<ChunkBlockRange>);
+
+impl Compress for ChunkRanges {
+    type Compressed = Vec<u8>;
+
+    fn compress_to_buf<B: BufMut + AsMut<[u8]>>(self, buf: &mut B) {
+        buf.put_slice(&rlp::encode_list::<ChunkBlockRange, _>(&self.0));
+    }
+}
+
+impl Decompress for ChunkRanges {
+    fn decompress<B: AsRef<[u8]>>(value: B) -> Result<Self, DatabaseError> {
+        rlp::Rlp::new(value.as_ref())
+            .as_list()
+            .map(ChunkRanges)
+            .map_err(|_| DatabaseError::Decode)
+    }
+}
+
+impl Compress for ChunkBlockRange {
+    type Compressed = Vec<u8>;
+
+    fn compress_to_buf<B: BufMut + AsMut<[u8]>>(self, buf: &mut B) {
+        buf.put_slice(&rlp::encode(&self));
+    }
+}
+
+impl Decompress for ChunkBlockRange {
+    fn decompress<B: AsRef<[u8]>>(value: B) -> Result<Self, DatabaseError> {
+        rlp::decode(value.as_ref()).map_err(|_| DatabaseError::Decode)
+    }
+}
+
+impl Compress for BatchStatus {
+    type Compressed = Vec<u8>;
+
+    fn compress_to_buf<B: BufMut + AsMut<[u8]>>(self, buf: &mut B) {
+        buf.put_slice(&rlp::encode(&self));
+    }
+}
+
+impl Decompress for BatchStatus {
+    fn decompress<B: AsRef<[u8]>>(value: B) -> Result<Self, DatabaseError> {
+        rlp::decode(value.as_ref()).map_err(|_| DatabaseError::Decode)
+    }
+}
+
+impl Compress for FinalizedRoots {
+    type Compressed = Vec<u8>;
+
+    fn compress_to_buf<B: BufMut + AsMut<[u8]>>(self, buf: &mut B) {
+        buf.put_slice(&rlp::encode(&self));
+    }
+}
+
+impl Decompress for FinalizedRoots {
+    fn decompress<B: AsRef<[u8]>>(value: B) -> Result<Self, DatabaseError> {
+        rlp::decode(value.as_ref()).map_err(|_| DatabaseError::Decode)
+    }
+}
+
+impl Compress for BlockContext {
+    type Compressed = Vec<u8>;
+
+    fn compress_to_buf<B: BufMut + AsMut<[u8]>>(self, buf: &mut B) {
+        buf.put_slice(&rlp::encode(&self));
+    }
+}
+
+impl Decompress for BlockContext {
+    fn decompress<B: AsRef<[u8]>>(value: B) -> Result<Self, DatabaseError> {
+        rlp::decode(value.as_ref()).map_err(|_| DatabaseError::Decode)
+    }
+}
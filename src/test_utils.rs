@@ -0,0 +1,73 @@
+//! Fixtures shared by the unit tests.
+
+use alloy_primitives::{Address, Bytes, U256};
+use ethers::types::{H256, U256 as EthersU256};
+
+use crate::{
+    batch_status::L1Provenance,
+    rollup_sync_service_util::{BlockContext, ChunkBlockRange},
+    storage::{BatchEvent, CommittedBatch},
+    sync_service::L1MessageTx,
+};
+
+pub fn message(queue_index: u64) -> L1MessageTx {
+    L1MessageTx {
+        queue_index,
+        gas: U256::from(100_000),
+        to: Address::repeat_byte(1),
+        value: U256::from(queue_index),
+        data: Bytes::from(vec![0xca, 0xfe]),
+        sender: Address::repeat_byte(2),
+    }
+}
+
+/// Provenance of an event emitted in `block_number`, 12 seconds per L1 block.
+pub fn provenance(block_number: u64) -> L1Provenance {
+    L1Provenance {
+        block_number,
+        tx_hash: H256::from_low_u64_be(block_number),
+        timestamp: block_number * 12,
+    }
+}
+
+pub fn chunk(start_block_number: u64, end_block_number: u64) -> ChunkBlockRange {
+    ChunkBlockRange {
+        start_block_number,
+        end_block_number,
+    }
+}
+
+pub fn block(number: u64) -> BlockContext {
+    BlockContext {
+        number,
+        timestamp: 1_700_000_000 + number * 3,
+        base_fee: EthersU256::from(1_000_000),
+        gas_limit: 10_000_000,
+        num_transactions: 2,
+        num_l1_messages: 1,
+    }
+}
+
+/// Batch committed in L1 block `l1_block` with the given chunks and a context for each of their
+/// blocks, popping the L1 messages `[parent_total, total)`.
+pub fn committed(
+    batch_index: u64,
+    chunks: Vec<ChunkBlockRange>,
+    parent_total: u64,
+    total: u64,
+    l1_block: u64,
+) -> BatchEvent {
+    let blocks = chunks
+        .iter()
+        .flat_map(|chunk| chunk.start_block_number..=chunk.end_block_number)
+        .map(block)
+        .collect();
+    BatchEvent::Committed(CommittedBatch {
+        batch_index,
+        chunks,
+        blocks,
+        parent_total_l1_messages_popped: parent_total,
+        total_l1_messages_popped: total,
+        provenance: provenance(l1_block),
+    })
+}
@@ -0,0 +1,435 @@
+use std::{fmt, path::Path};
+
+use anyhow::{bail, Result};
+use reth_db::{cursor::DbCursorRO, database::Database, transaction::DbTx};
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    db::{
+        count_l1_messages_in_range, get_batch, get_batch_total_l1_messages_popped,
+        get_first_l1_message_index, get_last_finalized_batch_index, open_db_read_only,
+    },
+    rollup_sync_service_util::ChunkBlockRange,
+    tables::BatchBlockRanges,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RangeViolationKind {
+    /// A chunk ends before it starts.
+    InvalidChunk,
+    ChunkGap,
+    ChunkOverlap,
+    BatchGap,
+    BatchOverlap,
+}
+
+impl RangeViolationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RangeViolationKind::InvalidChunk => "invalid_chunk",
+            RangeViolationKind::ChunkGap => "chunk_gap",
+            RangeViolationKind::ChunkOverlap => "chunk_overlap",
+            RangeViolationKind::BatchGap => "batch_gap",
+            RangeViolationKind::BatchOverlap => "batch_overlap",
+        }
+    }
+}
+
+/// A break in the L2 block ranges: every chunk and every batch must start right after the
+/// previous one ends.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeViolation {
+    pub batch_index: u64,
+    pub kind: RangeViolationKind,
+    /// Index of the offending chunk within the batch, `None` for violations between batches.
+    pub chunk_index: Option<usize>,
+    /// Block right after the previous range, `None` for an invalid chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_start_block: Option<u64>,
+    pub start_block: u64,
+    /// End block of an invalid chunk, `None` for the other violations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_block: Option<u64>,
+}
+
+impl fmt::Display for RangeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.chunk_index, self.expected_start_block, self.end_block) {
+            (Some(chunk_index), _, Some(end_block)) => write!(
+                f,
+                "batch {} chunk {}: {} (starts at block {}, ends at {})",
+                self.batch_index,
+                chunk_index,
+                self.kind.as_str(),
+                self.start_block,
+                end_block
+            ),
+            (Some(chunk_index), Some(expected_start_block), _) => write!(
+                f,
+                "batch {} chunk {}: {} (expected start block {}, got {})",
+                self.batch_index,
+                chunk_index,
+                self.kind.as_str(),
+                expected_start_block,
+                self.start_block
+            ),
+            (_, expected_start_block, _) => write!(
+                f,
+                "batch {}: {} with batch {} (expected start block {}, got {})",
+                self.batch_index,
+                self.kind.as_str(),
+                self.batch_index - 1,
+                expected_start_block.unwrap_or_default(),
+                self.start_block
+            ),
+        }
+    }
+}
+
+fn violation(
+    batch_index: u64,
+    chunk_index: Option<usize>,
+    expected_start_block: u64,
+    start_block: u64,
+) -> Option<RangeViolation> {
+    let kind = match chunk_index {
+        Some(_) if start_block > expected_start_block => RangeViolationKind::ChunkGap,
+        Some(_) if start_block < expected_start_block => RangeViolationKind::ChunkOverlap,
+        None if start_block > expected_start_block => RangeViolationKind::BatchGap,
+        None if start_block < expected_start_block => RangeViolationKind::BatchOverlap,
+        _ => return None,
+    };
+    Some(RangeViolation {
+        batch_index,
+        kind,
+        chunk_index,
+        expected_start_block: Some(expected_start_block),
+        start_block,
+        end_block: None,
+    })
+}
+
+/// Checks that every chunk of a batch is a valid range that starts right after the previous one.
+pub fn check_chunks(batch_index: u64, chunks: &[ChunkBlockRange]) -> Vec<RangeViolation> {
+    let mut violations = vec![];
+    for (chunk_index, chunk) in chunks.iter().enumerate() {
+        if chunk.start_block_number > chunk.end_block_number {
+            violations.push(RangeViolation {
+                batch_index,
+                kind: RangeViolationKind::InvalidChunk,
+                chunk_index: Some(chunk_index),
+                expected_start_block: None,
+                start_block: chunk.start_block_number,
+                end_block: Some(chunk.end_block_number),
+            });
+        }
+        if let Some(previous) = chunk_index.checked_sub(1).map(|index| &chunks[index]) {
+            violations.extend(violation(
+                batch_index,
+                Some(chunk_index),
+                previous.end_block_number + 1,
+                chunk.start_block_number,
+            ));
+        }
+    }
+    violations
+}
+
+/// Checks that a batch starts right after the previous batch, which ends at
+/// `previous_last_block`.
+pub fn check_follows(
+    batch_index: u64,
+    first_block: u64,
+    previous_last_block: u64,
+) -> Option<RangeViolation> {
+    violation(batch_index, None, previous_last_block + 1, first_block)
+}
+
+/// A batch whose popped L1 messages don't line up with the previous batch or with the local
+/// copy of the L1 message queue.
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum L1MessageViolation {
+    /// The parent batch header records a different total than the previous batch popped.
+    PoppedMismatch {
+        batch_index: u64,
+        parent_total: u64,
+        previous_total: u64,
+    },
+    /// Messages the batch pops are not in the local queue.
+    MissingMessages {
+        batch_index: u64,
+        first_queue_index: u64,
+        last_queue_index: u64,
+        missing: u64,
+    },
+}
+
+impl L1MessageViolation {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            L1MessageViolation::PoppedMismatch { .. } => "popped_mismatch",
+            L1MessageViolation::MissingMessages { .. } => "missing_messages",
+        }
+    }
+}
+
+impl fmt::Display for L1MessageViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            L1MessageViolation::PoppedMismatch {
+                batch_index,
+                parent_total,
+                previous_total,
+            } => write!(
+                f,
+                "batch {}: parent header records {} L1 messages popped, batch {} popped {}",
+                batch_index,
+                parent_total,
+                batch_index - 1,
+                previous_total
+            ),
+            L1MessageViolation::MissingMessages {
+                batch_index,
+                first_queue_index,
+                last_queue_index,
+                missing,
+            } => write!(
+                f,
+                "batch {}: {} of the L1 messages {} to {} it pops are not synced",
+                batch_index, missing, first_queue_index, last_queue_index
+            ),
+        }
+    }
+}
+
+pub fn check_parent_popped(
+    batch_index: u64,
+    parent_total: u64,
+    previous_total: u64,
+) -> Option<L1MessageViolation> {
+    (parent_total != previous_total).then_some(L1MessageViolation::PoppedMismatch {
+        batch_index,
+        parent_total,
+        previous_total,
+    })
+}
+
+/// Checks that the messages with a queue index in `[from, to)`, which the batch pops, are all
+/// stored, given the number that was found.
+pub fn check_popped_messages(
+    batch_index: u64,
+    from: u64,
+    to: u64,
+    stored: u64,
+) -> Option<L1MessageViolation> {
+    let popped = to.saturating_sub(from);
+    (stored < popped).then(|| L1MessageViolation::MissingMessages {
+        batch_index,
+        first_queue_index: from,
+        last_queue_index: to - 1,
+        missing: popped - stored,
+    })
+}
+
+/// Checks the block ranges and popped L1 messages of every stored batch and prints the
+/// violations. Chunks of pruned batches can't be checked, their block range still is, and
+/// pruned L1 messages are not reported as missing.
+pub fn run_verify(datadir: &Path) -> Result<()> {
+    let db = open_db_read_only(datadir)?;
+    let tx = db.tx()?;
+    // Messages below the first stored one were pruned, or all popped by finalized batches if
+    // none are left.
+    let first_stored_message = match get_first_l1_message_index(&tx)? {
+        Some(queue_index) => queue_index,
+        None => match get_last_finalized_batch_index(&tx)? {
+            Some(last_finalized) => {
+                get_batch_total_l1_messages_popped(&tx, last_finalized)?.unwrap_or(0)
+            }
+            None => 0,
+        },
+    };
+
+    let mut range_violations = vec![];
+    let mut message_violations = vec![];
+    let mut checked = 0;
+    let mut previous: Option<(u64, u64, Option<u64>)> = None;
+    let mut cursor = tx.cursor_read::<BatchBlockRanges>()?;
+    for entry in cursor.walk(None)? {
+        let (batch_index, range) = entry?;
+        let total_popped = get_batch_total_l1_messages_popped(&tx, batch_index)?;
+        if let Some(batch) = get_batch(&tx, batch_index)? {
+            range_violations.extend(check_chunks(batch_index, &batch.chunks));
+        }
+        // Batches committed without chunks have no block range to compare against.
+        if let Some((previous_index, previous_last_block, previous_popped)) = previous {
+            if previous_index + 1 == batch_index {
+                range_violations.extend(check_follows(
+                    batch_index,
+                    range.start_block_number,
+                    previous_last_block,
+                ));
+                if let (Some(from), Some(to)) = (previous_popped, total_popped) {
+                    let from = from.max(first_stored_message);
+                    let stored = if from < to {
+                        count_l1_messages_in_range(&tx, from, to - 1)?
+                    } else {
+                        0
+                    };
+                    message_violations.extend(check_popped_messages(batch_index, from, to, stored));
+                }
+            }
+        }
+        previous = Some((batch_index, range.end_block_number, total_popped));
+        checked += 1;
+    }
+
+    for violation in &range_violations {
+        println!("{}", serde_json::to_string(violation)?);
+    }
+    for violation in &message_violations {
+        println!("{}", serde_json::to_string(violation)?);
+    }
+    if !range_violations.is_empty() || !message_violations.is_empty() {
+        bail!(
+            "Found {} block range and {} L1 message violations in {} batches",
+            range_violations.len(),
+            message_violations.len(),
+            checked
+        );
+    }
+    info!(
+        "Block ranges and L1 messages of {} batches are consistent",
+        checked
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::chunk;
+
+    fn kinds(violations: &[RangeViolation]) -> Vec<(Option<usize>, RangeViolationKind)> {
+        violations
+            .iter()
+            .map(|violation| (violation.chunk_index, violation.kind))
+            .collect()
+    }
+
+    #[test]
+    fn contiguous_chunks() {
+        assert!(check_chunks(1, &[]).is_empty());
+        assert!(check_chunks(1, &[chunk(10, 10)]).is_empty());
+        assert!(check_chunks(1, &[chunk(10, 14), chunk(15, 15), chunk(16, 20)]).is_empty());
+    }
+
+    #[test]
+    fn chunk_gap_and_overlap() {
+        let violations = check_chunks(1, &[chunk(10, 14), chunk(16, 20), chunk(20, 25)]);
+        assert_eq!(
+            kinds(&violations),
+            [
+                (Some(1), RangeViolationKind::ChunkGap),
+                (Some(2), RangeViolationKind::ChunkOverlap),
+            ]
+        );
+        assert_eq!(violations[0].expected_start_block, Some(15));
+        assert_eq!(violations[0].start_block, 16);
+        assert_eq!(violations[1].expected_start_block, Some(21));
+        assert_eq!(violations[1].start_block, 20);
+        assert_eq!(
+            violations[0].to_string(),
+            "batch 1 chunk 1: chunk_gap (expected start block 15, got 16)"
+        );
+    }
+
+    #[test]
+    fn inverted_chunk() {
+        let violations = check_chunks(1, &[chunk(10, 14), chunk(15, 12)]);
+        assert_eq!(
+            kinds(&violations),
+            [(Some(1), RangeViolationKind::InvalidChunk)]
+        );
+        let violation = &violations[0];
+        assert_eq!(violation.expected_start_block, None);
+        assert_eq!(violation.start_block, 15);
+        assert_eq!(violation.end_block, Some(12));
+        assert_eq!(
+            violation.to_string(),
+            "batch 1 chunk 1: invalid_chunk (starts at block 15, ends at 12)"
+        );
+        assert_eq!(
+            serde_json::to_value(violation).unwrap(),
+            serde_json::json!({
+                "batchIndex": 1,
+                "kind": "invalidChunk",
+                "chunkIndex": 1,
+                "startBlock": 15,
+                "endBlock": 12,
+            })
+        );
+    }
+
+    #[test]
+    fn batch_follows_previous_batch() {
+        assert!(check_follows(2, 21, 20).is_none());
+
+        let gap = check_follows(2, 22, 20).unwrap();
+        assert_eq!(gap.kind, RangeViolationKind::BatchGap);
+        assert_eq!(gap.chunk_index, None);
+        assert_eq!(gap.expected_start_block, Some(21));
+        assert_eq!(
+            gap.to_string(),
+            "batch 2: batch_gap with batch 1 (expected start block 21, got 22)"
+        );
+
+        let overlap = check_follows(2, 20, 20).unwrap();
+        assert_eq!(overlap.kind, RangeViolationKind::BatchOverlap);
+        assert_eq!(overlap.start_block, 20);
+    }
+
+    #[test]
+    fn parent_popped_matches_previous_batch() {
+        assert!(check_parent_popped(2, 10, 10).is_none());
+
+        let violation = check_parent_popped(2, 10, 12).unwrap();
+        assert_eq!(violation.kind(), "popped_mismatch");
+        assert_eq!(
+            violation.to_string(),
+            "batch 2: parent header records 10 L1 messages popped, batch 1 popped 12"
+        );
+    }
+
+    #[test]
+    fn popped_messages_are_stored() {
+        assert!(check_popped_messages(2, 10, 15, 5).is_none());
+        // Batches that pop no messages have nothing to check.
+        assert!(check_popped_messages(2, 10, 10, 0).is_none());
+        assert!(check_popped_messages(2, 0, 0, 0).is_none());
+
+        let violation = check_popped_messages(2, 10, 15, 3).unwrap();
+        assert_eq!(violation.kind(), "missing_messages");
+        assert_eq!(
+            violation.to_string(),
+            "batch 2: 2 of the L1 messages 10 to 14 it pops are not synced"
+        );
+        assert_eq!(
+            serde_json::to_value(violation).unwrap(),
+            serde_json::json!({
+                "kind": "missingMessages",
+                "batchIndex": 2,
+                "firstQueueIndex": 10,
+                "lastQueueIndex": 14,
+                "missing": 2,
+            })
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use ethers::{types::H256, utils::keccak256};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::WithdrawalProofArgs,
+    db::{get_latest_finalized_roots, open_db_read_only, FinalizedBatchRoots},
+    db_command::print_json,
+};
+
+fn hash_pair(left: H256, right: H256) -> H256 {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(left.as_bytes());
+    buf[32..].copy_from_slice(right.as_bytes());
+    H256::from(keccak256(buf))
+}
+
+/// Append-only Merkle tree of the L2 withdrawal message hashes, built like the
+/// `L2MessageQueue` contract builds it: the tree is as deep as the last leaf index needs, and
+/// missing nodes are hashes of empty subtrees. The leaf index is the message nonce.
+#[derive(Debug, Default)]
+pub struct WithdrawTrie {
+    /// Hash of an empty subtree of each height.
+    zero_hashes: Vec<H256>,
+    /// Last left node of each height, enough to compute the root after every append.
+    branches: Vec<H256>,
+    leaves: u64,
+    root: H256,
+}
+
+impl WithdrawTrie {
+    /// Appends the hash of the next message and returns the new root.
+    pub fn append(&mut self, message_hash: H256) -> H256 {
+        let mut index = self.leaves;
+        let mut hash = message_hash;
+        let mut height = 0;
+        while index != 0 {
+            if index % 2 == 0 {
+                self.set_branch(height, hash);
+                hash = hash_pair(hash, self.zero_hash(height));
+            } else {
+                hash = hash_pair(self.branches[height], hash);
+            }
+            height += 1;
+            index >>= 1;
+        }
+        self.set_branch(height, hash);
+        self.leaves += 1;
+        self.root = hash;
+        hash
+    }
+
+    fn set_branch(&mut self, height: usize, hash: H256) {
+        if self.branches.len() <= height {
+            self.branches.resize(height + 1, H256::zero());
+        }
+        self.branches[height] = hash;
+    }
+
+    fn zero_hash(&mut self, height: usize) -> H256 {
+        while self.zero_hashes.len() <= height {
+            let zero_hash = match self.zero_hashes.last() {
+                Some(below) => hash_pair(*below, *below),
+                None => H256::zero(),
+            };
+            self.zero_hashes.push(zero_hash);
+        }
+        self.zero_hashes[height]
+    }
+
+    /// Sibling hashes from the leaf at `nonce` up to the root of the tree of `leaves`, in the
+    /// format `WithdrawTrieVerifier` on L1 expects.
+    pub fn proof(leaves: &[H256], nonce: u64) -> Option<Vec<H256>> {
+        let mut index = usize::try_from(nonce).ok()?;
+        if index >= leaves.len() {
+            return None;
+        }
+        let mut trie = WithdrawTrie::default();
+        let mut layer = leaves.to_vec();
+        let mut proof = vec![];
+        let mut height = 0;
+        while layer.len() > 1 {
+            let zero_hash = trie.zero_hash(height);
+            proof.push(layer.get(index ^ 1).copied().unwrap_or(zero_hash));
+            layer = layer
+                .chunks(2)
+                .map(|pair| hash_pair(pair[0], pair.get(1).copied().unwrap_or(zero_hash)))
+                .collect();
+            index /= 2;
+            height += 1;
+        }
+        Some(proof)
+    }
+
+    /// Same check as `WithdrawTrieVerifier.verifyMerkleProof`.
+    pub fn verify(root: H256, message_hash: H256, nonce: u64, proof: &[H256]) -> bool {
+        let mut hash = message_hash;
+        let mut index = nonce;
+        for sibling in proof {
+            hash = if index % 2 == 0 {
+                hash_pair(hash, *sibling)
+            } else {
+                hash_pair(*sibling, hash)
+            };
+            index /= 2;
+        }
+        hash == root
+    }
+}
+
+/// Proof of a withdrawal against the withdraw root of the latest finalized batch. The proof is
+/// only present if the withdrawal is claimable, i.e. included in the finalized withdraw trie.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawalProof {
+    pub nonce: u64,
+    pub message_hash: H256,
+    pub claimable: bool,
+    pub finalized: FinalizedBatchRoots,
+    /// Number of leaves in the finalized withdraw trie.
+    pub finalized_leaves: u64,
+    pub proof: Option<Vec<H256>>,
+}
+
+/// Finds the prefix of `leaves` whose root is the finalized withdraw root and proves the
+/// message at `nonce` against it. Fails if no prefix matches, which means the leaves are
+/// incomplete or don't belong to this chain.
+pub fn build_withdrawal_proof(
+    leaves: &[H256],
+    nonce: u64,
+    finalized: FinalizedBatchRoots,
+) -> Result<WithdrawalProof> {
+    let Some(message_hash) = usize::try_from(nonce)
+        .ok()
+        .and_then(|index| leaves.get(index))
+        .copied()
+    else {
+        bail!("No leaf for nonce {}, {} leaves given", nonce, leaves.len());
+    };
+
+    let mut trie = WithdrawTrie::default();
+    let Some(finalized_leaves) = leaves
+        .iter()
+        .position(|leaf| trie.append(*leaf) == finalized.roots.withdraw_root)
+        .map(|position| position + 1)
+    else {
+        bail!(
+            "No prefix of the {} leaves has the withdraw root {:?} of batch {}",
+            leaves.len(),
+            finalized.roots.withdraw_root,
+            finalized.batch_index
+        );
+    };
+
+    let claimable = (nonce as usize) < finalized_leaves;
+    let proof = if claimable {
+        let proof = WithdrawTrie::proof(&leaves[..finalized_leaves], nonce)
+            .expect("Nonce is within the finalized leaves");
+        debug_assert!(WithdrawTrie::verify(
+            finalized.roots.withdraw_root,
+            message_hash,
+            nonce,
+            &proof
+        ));
+        Some(proof)
+    } else {
+        None
+    };
+    Ok(WithdrawalProof {
+        nonce,
+        message_hash,
+        claimable,
+        finalized,
+        finalized_leaves: finalized_leaves as u64,
+        proof,
+    })
+}
+
+/// Reads withdraw trie leaves from a file with one message hash per line, ordered by nonce.
+pub fn read_leaves(path: &Path) -> Result<Vec<H256>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(nonce, line)| {
+            H256::from_str(line).with_context(|| format!("Invalid leaf for nonce {}", nonce))
+        })
+        .collect()
+}
+
+/// Proves a withdrawal from a leaves file against the finalized withdraw root in the database.
+pub fn run_withdrawal_proof(datadir: &Path, args: WithdrawalProofArgs) -> Result<()> {
+    let leaves = read_leaves(&args.leaves)?;
+    let db = open_db_read_only(datadir)?;
+    let Some(finalized) = get_latest_finalized_roots(&db.tx()?)? else {
+        bail!("No finalized withdraw root synced yet");
+    };
+    let proof = build_withdrawal_proof(&leaves, args.nonce, finalized)?;
+    print_json(&proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch_status::FinalizedRoots;
+
+    /// Roots of `L2MessageQueue` after each of the first five leaves of `leaves` is appended.
+    const MESSAGE_QUEUE_ROOTS: [&str; 5] = [
+        "0x0000000000000000000000000000000000000000000000000000000000000001",
+        "0xe90b7bceb6e7df5418fb78d8ee546e97c83a08bbccc01a0644d599ccd2a7c2e0",
+        "0x222ff5e0b5877792c2bc1670e2ccd0c2c97cd7bb1672a57d598db05092d3d72c",
+        "0xa9bb8c3f1f12e9aa903a50c47f314b57610a3ab32f2d463293f58836def38d36",
+        "0xeea4d170e1f99e2a83c5ed4fff339b60f5faa616b7ecf188fb88394928103d63",
+    ];
+
+    /// The leaf of nonce `n` is the message hash `n + 1`.
+    fn leaves(count: u64) -> Vec<H256> {
+        (1..=count).map(H256::from_low_u64_be).collect()
+    }
+
+    fn root(leaves: &[H256]) -> H256 {
+        let mut trie = WithdrawTrie::default();
+        leaves.iter().map(|leaf| trie.append(*leaf)).last().unwrap()
+    }
+
+    fn finalized(withdraw_root: H256) -> FinalizedBatchRoots {
+        FinalizedBatchRoots {
+            batch_index: 3,
+            last_block: 100,
+            roots: FinalizedRoots {
+                state_root: H256::zero(),
+                withdraw_root,
+            },
+        }
+    }
+
+    #[test]
+    fn append_matches_message_queue_roots() {
+        let mut trie = WithdrawTrie::default();
+        for (leaf, expected) in leaves(5).into_iter().zip(MESSAGE_QUEUE_ROOTS) {
+            assert_eq!(trie.append(leaf), H256::from_str(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn proofs_verify_against_root() {
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            let root = root(&leaves);
+            for nonce in 0..count {
+                let message_hash = leaves[nonce as usize];
+                let proof = WithdrawTrie::proof(&leaves, nonce).unwrap();
+                assert!(WithdrawTrie::verify(root, message_hash, nonce, &proof));
+                assert!(!WithdrawTrie::verify(
+                    root,
+                    H256::repeat_byte(0xff),
+                    nonce,
+                    &proof
+                ));
+            }
+            assert!(WithdrawTrie::proof(&leaves, count).is_none());
+        }
+    }
+
+    #[test]
+    fn nonce_past_finalized_prefix_is_not_claimable() {
+        let leaves = leaves(5);
+        let finalized_root = root(&leaves[..3]);
+
+        let proof = build_withdrawal_proof(&leaves, 3, finalized(finalized_root)).unwrap();
+        assert!(!proof.claimable);
+        assert_eq!(proof.finalized_leaves, 3);
+        assert!(proof.proof.is_none());
+
+        let proof = build_withdrawal_proof(&leaves, 2, finalized(finalized_root)).unwrap();
+        assert!(proof.claimable);
+        assert!(WithdrawTrie::verify(
+            finalized_root,
+            proof.message_hash,
+            2,
+            &proof.proof.unwrap()
+        ));
+    }
+
+    #[test]
+    fn leaves_without_finalized_root_are_rejected() {
+        let err =
+            build_withdrawal_proof(&leaves(5), 0, finalized(H256::repeat_byte(0xff))).unwrap_err();
+        assert!(err.to_string().starts_with("No prefix of the 5 leaves"));
+    }
+}